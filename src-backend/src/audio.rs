@@ -0,0 +1,217 @@
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use crate::mmio::{set_word_byte, word_byte, DeviceOutput, MemoryView, MmioDevice};
+
+// Samples are played from a ring buffer in program memory.
+//   0xFFFF0200 buffer address
+//   0xFFFF0204 buffer size in bytes
+//   0xFFFF0208 write offset, moved by the program after it fills in samples
+//   0xFFFF020C read offset (read only), how far playback has reached
+//   0xFFFF0210 sample rate in Hz
+//   0xFFFF0214 bit 0 enables playback, bit 1 selects 16 bit signed samples (8 bit unsigned otherwise)
+pub const AUDIO_ADDRESS: u32 = 0xFFFF0200;
+pub const AUDIO_END: u32 = 0xFFFF0218;
+const AUDIO_RANGE: Range<u32> = AUDIO_ADDRESS..AUDIO_END;
+
+pub const AUDIO_ENABLE: u32 = 0b01;
+pub const AUDIO_16_BIT: u32 = 0b10;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 8000;
+
+#[derive(Clone, Serialize)]
+pub struct AudioChunk {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+}
+
+pub struct PcmState {
+    address: u32,
+    size: u32,
+    write: u32,
+    read: u32,
+    sample_rate: u32,
+    control: u32,
+    // Time playback started and samples due since then, for pacing with TimeHandler.
+    start: Option<Duration>,
+    played: u64,
+}
+
+#[derive(Default)]
+pub struct PcmHandler {
+    pub state: Arc<Mutex<PcmState>>,
+}
+
+impl Default for PcmState {
+    fn default() -> PcmState {
+        PcmState {
+            address: 0,
+            size: 0,
+            write: 0,
+            read: 0,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            control: 0,
+            start: None,
+            played: 0,
+        }
+    }
+}
+
+// Samples taken off the ring, read from memory once the state lock is released.
+struct PcmRead {
+    address: u32,
+    size: u64,
+    offset: u64,
+    count: u64,
+    bytes: u64,
+    sample_rate: u32,
+}
+
+impl PcmRead {
+    fn read_sample(&self, memory: &dyn MemoryView, offset: u64) -> i16 {
+        let byte = |index: u64| {
+            let point = self.address.wrapping_add(((offset + index) % self.size) as u32);
+
+            memory.view(point).unwrap_or(0)
+        };
+
+        if self.bytes == 2 {
+            i16::from_le_bytes([byte(0), byte(1)])
+        } else {
+            ((byte(0) as i16) - 128) << 8
+        }
+    }
+
+    fn chunk(&self, memory: &dyn MemoryView) -> AudioChunk {
+        let samples = (0 .. self.count)
+            .map(|i| self.read_sample(memory, self.offset + i * self.bytes))
+            .collect();
+
+        AudioChunk { samples, sample_rate: self.sample_rate }
+    }
+}
+
+impl PcmState {
+    fn sample_bytes(&self) -> u64 {
+        if self.control & AUDIO_16_BIT != 0 { 2 } else { 1 }
+    }
+
+    // A buffer over the device's own registers would play them back, it's treated as empty.
+    fn buffer_in_device(&self) -> bool {
+        let start = self.address as u64;
+        let end = start + self.size as u64;
+
+        start < AUDIO_END as u64 && end > AUDIO_ADDRESS as u64
+    }
+
+    // Takes the samples that should have played by now, or None if there's nothing to play.
+    fn drain(&mut self, now: Option<Duration>) -> Option<PcmRead> {
+        let enabled = self.control & AUDIO_ENABLE != 0 && self.size > 0 && !self.buffer_in_device();
+
+        let (Some(now), true) = (now, enabled) else {
+            self.start = None;
+
+            return None
+        };
+
+        let start = *self.start.get_or_insert(now);
+        let elapsed = u64::try_from(now.saturating_sub(start).as_micros()).unwrap_or(u64::MAX);
+
+        let due_total = elapsed.saturating_mul(self.sample_rate as u64) / 1_000_000;
+        let due = due_total.saturating_sub(self.played);
+
+        // Coming back from a pause (or a very slow batch), start over instead of playing a burst.
+        if due > (self.sample_rate / 4) as u64 {
+            self.start = Some(now);
+            self.played = 0;
+
+            return None
+        }
+
+        // Falling behind the program just drops the gap.
+        self.played = due_total;
+
+        let size = self.size as u64;
+        let read = self.read as u64 % size;
+        let bytes = self.sample_bytes();
+        let available = (self.write as u64 % size + size - read) % size / bytes;
+        let count = available.min(due);
+
+        if count == 0 {
+            return None
+        }
+
+        self.read = ((read + count * bytes) % size) as u32;
+
+        Some(PcmRead {
+            address: self.address,
+            size,
+            offset: read,
+            count,
+            bytes,
+            sample_rate: self.sample_rate,
+        })
+    }
+}
+
+impl MmioDevice for PcmHandler {
+    fn name(&self) -> &'static str {
+        "audio"
+    }
+
+    fn ranges(&self) -> &[Range<u32>] {
+        std::slice::from_ref(&AUDIO_RANGE)
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let state = self.state.lock().unwrap();
+
+        let offset = address.wrapping_sub(AUDIO_ADDRESS);
+
+        let value = match offset / 4 {
+            0 => state.address,
+            1 => state.size,
+            2 => state.write,
+            3 => state.read,
+            4 => state.sample_rate,
+            5 => state.control,
+            _ => return Err(MemoryUnmapped(address))
+        };
+
+        Ok(word_byte(value, offset))
+    }
+
+    fn write(&self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let offset = address.wrapping_sub(AUDIO_ADDRESS);
+
+        match offset / 4 {
+            0 => set_word_byte(&mut state.address, offset, value),
+            1 => {
+                set_word_byte(&mut state.size, offset, value);
+
+                state.read = 0;
+            }
+            2 => set_word_byte(&mut state.write, offset, value),
+            3 => {} // read only
+            4 => set_word_byte(&mut state.sample_rate, offset, value),
+            5 => set_word_byte(&mut state.control, offset, value),
+            _ => return Err(MemoryUnmapped(address))
+        }
+
+        Ok(())
+    }
+
+    fn update(&self, memory: &dyn MemoryView, now: Option<Duration>, output: &mut DeviceOutput) {
+        // The lock is released first, reading memory in the device page comes back through read.
+        let read = self.state.lock().unwrap().drain(now);
+
+        if let Some(read) = read {
+            output.audio.push(read.chunk(memory))
+        }
+    }
+}
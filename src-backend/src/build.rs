@@ -2,7 +2,6 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Cursor;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
 use titan::assembler::binary::{Binary, RegionFlags};
 use titan::assembler::line_details::LineDetails;
 use titan::assembler::string::{assemble_from, assemble_from_path, SourceError};
@@ -12,7 +11,13 @@ use titan::cpu::{Memory, State};
 use titan::execution::elf::inspection::Inspection;
 use titan::elf::Elf;
 use titan::elf::program::ProgramHeaderFlags;
-use crate::keyboard::{KeyboardHandler, KeyboardState, KEYBOARD_SELECTOR};
+use crate::device::{mount_stack, region_end};
+use crate::diagnostics::{collect_errors, collect_warnings, Diagnostic, SourcePosition};
+use crate::elf_debug::{append_debug_info, read_debug_info, ElfDebugInfo};
+use crate::raw_image::{binary_elf, BinaryFormat};
+use crate::layout::MemoryLayout;
+use crate::symbols::{symbol_table, Symbol};
+use crate::mmio::{DeviceConfig, MmioDevices, MmioHandler};
 
 pub const TIME_TRAVEL_HISTORY_SIZE: usize = 1000;
 
@@ -24,20 +29,24 @@ pub struct LineMarker {
 
 #[derive(Serialize)]
 pub struct Breakpoint {
-    line: usize,
-    pcs: Vec<u32>,
+    pub(crate) line: usize,
+    pub(crate) pcs: Vec<u32>,
 }
 
 #[derive(Serialize)]
 #[serde(tag = "status")]
 pub enum AssemblerResult {
+    // marker, message and body describe the first error, diagnostics has every error and warning.
     Error {
         marker: Option<LineMarker>,
         message: String,
         body: Option<String>,
+        diagnostics: Vec<Diagnostic>,
     },
     Success {
         breakpoints: Vec<Breakpoint>,
+        symbols: Vec<Symbol>,
+        diagnostics: Vec<Diagnostic>,
     },
 }
 
@@ -63,7 +72,10 @@ impl AssemblerResult {
     pub fn from_result_with_binary(
         result: Result<Binary, SourceError>,
         source: &str,
+        path: Option<&str>,
     ) -> (Option<Binary>, AssemblerResult) {
+        let warnings = collect_warnings(source, path);
+
         match result {
             Ok(binary) => {
                 let breakpoints = binary
@@ -75,7 +87,9 @@ impl AssemblerResult {
                     })
                     .collect();
 
-                (Some(binary), AssemblerResult::Success { breakpoints })
+                let symbols = symbol_table(&binary, source);
+
+                (Some(binary), AssemblerResult::Success { breakpoints, symbols, diagnostics: warnings })
             }
             Err(error) => {
                 let details = error
@@ -91,20 +105,52 @@ impl AssemblerResult {
                     .as_ref()
                     .map(|details| format!("{}\n{}", details.line_text, details.marker()));
 
+                let mut diagnostics = collect_errors(source, path, &error);
+                diagnostics.extend(warnings);
+
                 (
                     None,
                     AssemblerResult::Error {
                         marker,
                         message: format!("{}", error),
                         body,
+                        diagnostics,
                     },
                 )
             }
         }
     }
 
-    fn from_result(result: Result<Binary, SourceError>, source: &str) -> AssemblerResult {
-        Self::from_result_with_binary(result, source).1
+    fn from_result(result: Result<Binary, SourceError>, source: &str, path: Option<&str>) -> AssemblerResult {
+        Self::from_result_with_binary(result, source, path).1
+    }
+
+    // Moves every line back by count, for sources assembled with extra lines in front.
+    fn shift_lines(&mut self, count: usize) {
+        let shift_diagnostic = |diagnostic: &mut Diagnostic| {
+            diagnostic.start.line = diagnostic.start.line.saturating_sub(count);
+            diagnostic.end.line = diagnostic.end.line.saturating_sub(count);
+        };
+
+        match self {
+            AssemblerResult::Error { marker, diagnostics, .. } => {
+                if let Some(marker) = marker {
+                    marker.line = marker.line.saturating_sub(count)
+                }
+
+                diagnostics.iter_mut().for_each(shift_diagnostic);
+            }
+            AssemblerResult::Success { breakpoints, symbols, diagnostics } => {
+                breakpoints.retain(|breakpoint| breakpoint.line >= count);
+                breakpoints.iter_mut().for_each(|breakpoint| breakpoint.line -= count);
+
+                for location in symbols.iter_mut().filter_map(|symbol| symbol.location.as_mut()) {
+                    location.line = location.line.saturating_sub(count)
+                }
+
+                diagnostics.iter_mut().for_each(shift_diagnostic);
+            }
+        }
     }
 }
 
@@ -114,12 +160,17 @@ pub struct DisassembleResult {
 
     lines: Vec<String>,
     breakpoints: HashMap<u32, usize>,
+    // Labels from the ELF symbol table, if it has one.
+    symbols: Vec<Symbol>,
+    // Line of each label shown in the disassembly, with its address, so breakpoints can be set on symbols.
+    symbol_lines: HashMap<usize, u32>,
 }
 
 #[derive(Clone, Serialize)]
 pub struct PrintPayload<'a> {
     pub text: &'a str,
     pub error: bool,
+    pub log: bool,
 }
 
 pub fn assemble_text(text: &str, path: Option<&str>) -> Result<Binary, SourceError> {
@@ -133,10 +184,15 @@ pub fn assemble_text(text: &str, path: Option<&str>) -> Result<Binary, SourceErr
 
 pub fn create_elf_state<Mem: Memory + Mountable>(
     elf: &Elf,
-    heap_size: u32,
+    stack_size: u32,
+    layout: &MemoryLayout,
     mut memory: Mem
 ) -> State<Mem> {
+    let mut program = vec![];
+
     for header in &elf.program_headers {
+        program.push((header.virtual_address, region_end(header.virtual_address, header.data.len())));
+
         let region = Region {
             start: header.virtual_address,
             data: header.data.clone(),
@@ -145,71 +201,202 @@ pub fn create_elf_state<Mem: Memory + Mountable>(
         memory.mount(region)
     }
 
-    let heap_end = 0x7FFFFFFCu32;
-
-    let heap = Region {
-        start: heap_end - heap_size,
-        data: vec![0; heap_size as usize],
-    };
-
-    memory.mount(heap);
+    mount_stack(&mut memory, stack_size, layout, &program);
 
     let mut state = State::new(elf.header.program_entry, memory);
-    state.registers.line[29] = heap_end;
+    state.registers.line[29] = layout.stack_pointer;
 
     state
 }
 
-pub fn configure_keyboard(memory: &mut SectionMemory<KeyboardHandler>) -> Arc<Mutex<KeyboardState>> {
-    let handler = KeyboardHandler::new();
-    let keyboard = handler.state.clone();
+// Creates the devices enabled in config (keyboard, Digital Lab Sim, ...).
+// mount_listeners puts them in the layout's device page once the program is loaded.
+pub fn configure_keyboard(
+    memory: &mut SectionMemory<MmioHandler>,
+    config: &DeviceConfig,
+    layout: &MemoryLayout,
+) -> MmioDevices {
+    let devices = MmioDevices::new(config);
+
+    // Mark the data segment, heap and stack as "Writable"
+    for selector in layout.writable_selectors() {
+        memory.mount_writable(selector, 0xCC);
+    }
 
-    memory.mount_listen(KEYBOARD_SELECTOR as usize, handler);
+    devices
+}
 
-    // Mark heap as "Writable"
-    for selector in 0x1000..0x8000 {
-        memory.mount_writable(selector, 0xCC);
+// Assembles with the text and data segments placed where the layout expects them.
+pub fn assemble_layout(text: &str, path: Option<&str>, layout: &MemoryLayout) -> (Option<Binary>, AssemblerResult) {
+    if layout.is_default() {
+        return AssemblerResult::from_result_with_binary(assemble_text(text, path), text, path)
     }
 
-    keyboard
+    let header = layout.section_header();
+    let source = format!("{}{}", header, text);
+
+    let (binary, mut result) = AssemblerResult::from_result_with_binary(assemble_text(&source, path), &source, path);
+
+    result.shift_lines(header.matches('\n').count());
+
+    (binary, result)
 }
 
 pub fn assemble(text: &str, path: Option<&str>) -> AssemblerResult {
     let result = assemble_text(text, path);
 
-    AssemblerResult::from_result(result, text)
+    AssemblerResult::from_result(result, text, path)
 }
 
-pub fn disassemble(named: Option<&str>, bytes: Vec<u8>) -> DisassembleResult {
-    let elf = match Elf::read(&mut Cursor::new(bytes)) {
-        Ok(elf) => elf,
-        Err(error) => {
-            return DisassembleResult {
-                error: Some(error.to_string()),
-                lines: vec![],
-                breakpoints: HashMap::new(),
-            }
+impl DisassembleResult {
+    fn error(message: String) -> DisassembleResult {
+        DisassembleResult {
+            error: Some(message),
+            lines: vec![],
+            breakpoints: HashMap::new(),
+            symbols: vec![],
+            symbol_lines: HashMap::new(),
         }
+    }
+}
+
+// Raw images and hex files are wrapped in an ELF first, see BinaryFormat.
+pub fn disassemble(named: Option<&str>, bytes: Vec<u8>, format: &BinaryFormat) -> DisassembleResult {
+    let bytes = match binary_elf(bytes, format) {
+        Ok(bytes) => bytes,
+        Err(error) => return DisassembleResult::error(error)
+    };
+
+    let elf = match Elf::read(&mut Cursor::new(&bytes)) {
+        Ok(elf) => elf,
+        Err(error) => return DisassembleResult::error(error.to_string())
     };
 
+    let info = read_debug_info(&bytes);
+
     let inspection = Inspection::new(named, &elf);
+    let mut symbols = info.map(|info| info.symbols).unwrap_or_default();
+
+    // Labels go right above the line of their instruction.
+    let mut labels: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for (index, symbol) in symbols.iter_mut().enumerate() {
+        // Any source lines from the debug info don't apply to the disassembly.
+        symbol.location = None;
+
+        if let Some(line) = inspection.breakpoints.get(&symbol.address) {
+            labels.entry(*line).or_default().push(index)
+        }
+    }
+
+    let mut lines = vec![];
+    let mut moved = vec![];
+    let mut symbol_lines = HashMap::new();
+
+    for (index, line) in inspection.lines.into_iter().enumerate() {
+        for symbol in labels.remove(&index).unwrap_or_default() {
+            let symbol = &mut symbols[symbol];
+
+            symbol.location = Some(SourcePosition { line: lines.len(), column: 0 });
+            symbol_lines.insert(lines.len(), symbol.address);
+            lines.push(format!("{}:", symbol.name));
+        }
+
+        moved.push(lines.len());
+        lines.push(line);
+    }
+
+    let breakpoints = inspection.breakpoints
+        .into_iter()
+        .map(|(pc, line)| (pc, moved.get(line).copied().unwrap_or(line)))
+        .collect();
 
     DisassembleResult {
         error: None,
-        lines: inspection.lines,
-        breakpoints: inspection.breakpoints,
+        lines,
+        breakpoints,
+        symbols,
+        symbol_lines,
     }
 }
 
-pub fn assemble_binary(text: &str, path: Option<&str>) -> (Option<Vec<u8>>, AssemblerResult) {
-    let result = assemble_text(text, path);
-    let (binary, result) = AssemblerResult::from_result_with_binary(result, text);
+// Source breakpoints and named labels for a loaded ELF, empty if it has no debug sections.
+pub fn elf_result(info: Option<ElfDebugInfo>) -> AssemblerResult {
+    let breakpoints = info.as_ref()
+        .filter(|info| info.source.is_some())
+        .map(|info| {
+            info.line_pcs()
+                .into_iter()
+                .map(|(line, pcs)| Breakpoint { line, pcs })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    AssemblerResult::Success {
+        breakpoints,
+        symbols: info.map(|info| info.symbols).unwrap_or_default(),
+        diagnostics: vec![],
+    }
+}
+
+// The ELF's own entry is kept when it points at code. Otherwise compiled programs start at
+// __start (GNU ld's default) or _start, or at main when there is neither. Returning from main ends the program.
+pub fn setup_elf_entry<Mem: Memory>(
+    state: &mut State<Mem>, elf: &Elf, info: Option<&ElfDebugInfo>, finished_pcs: &[u32]
+) {
+    let entry = elf.header.program_entry;
+
+    let executable = elf.program_headers.iter().any(|header| {
+        header.flags.contains(ProgramHeaderFlags::EXECUTABLE)
+            && header.virtual_address <= entry
+            && ((entry - header.virtual_address) as usize) < header.data.len()
+    });
+
+    let Some(info) = info.filter(|_| !executable) else { return };
+
+    if let Some(start) = info.symbol("__start").or_else(|| info.symbol("_start")) {
+        state.registers.pc = start.address
+    } else if let Some(main) = info.symbol("main") {
+        state.registers.pc = main.address;
+
+        if let Some(end) = finished_pcs.first() {
+            state.registers.line[31] = *end
+        }
+    }
+}
+
+// Taken from the assembler's result, so lines are already in the source's own numbering.
+// The source itself is only embedded when asked for, exported files might be handed out.
+fn debug_info(breakpoints: &[Breakpoint], symbols: &[Symbol], source: Option<&str>) -> ElfDebugInfo {
+    let mut lines: Vec<(u32, u32)> = breakpoints
+        .iter()
+        .flat_map(|breakpoint| {
+            let line = breakpoint.line as u32;
+
+            breakpoint.pcs.iter().map(move |pc| (*pc, line))
+        })
+        .collect();
+
+    lines.sort();
+
+    ElfDebugInfo {
+        symbols: symbols.to_vec(),
+        lines,
+        source: source.map(|source| source.to_string()),
+    }
+}
+
+pub fn assemble_binary(
+    text: &str, path: Option<&str>, layout: &MemoryLayout, include_source: bool
+) -> (Option<Vec<u8>>, AssemblerResult) {
+    let (binary, result) = assemble_layout(text, path, layout);
 
-    let Some(binary) = binary else {
+    let (Some(binary), AssemblerResult::Success { breakpoints, symbols, .. }) = (binary, &result) else {
         return (None, result)
     };
 
     let elf: Elf = binary.create_elf();
+    let info = debug_info(breakpoints, symbols, include_source.then_some(text));
 
     let mut out: Vec<u8> = vec![];
     let mut cursor = Cursor::new(&mut out);
@@ -221,9 +408,10 @@ pub fn assemble_binary(text: &str, path: Option<&str>) -> (Option<Vec<u8>>, Asse
                 marker: None,
                 message: error.to_string(),
                 body: None,
+                diagnostics: vec![],
             },
         );
     }
 
-    (Some(out), result)
+    (Some(append_debug_info(out, &info)), result)
 }
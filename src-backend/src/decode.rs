@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::io::Cursor;
 use serde::Serialize;
 use titan::unit::instruction::{InstructionDecoder, InstructionParameter};
 use num::ToPrimitive;
 use titan::elf::Elf;
 use titan::execution::elf::detailed_inspection::{make_inspection_lines, InspectionLine};
+use crate::elf_debug::read_debug_info;
+use crate::raw_image::{binary_elf, BinaryFormat};
 
 #[derive(Serialize)]
 #[serde(tag="type", content="value")]
@@ -55,26 +58,45 @@ pub enum InspectionItem {
     Label { name: String },
 }
 
-pub fn detailed_disassemble(bytes: Vec<u8>) -> Result<Vec<InspectionItem>, String> {
-    let elf = Elf::read(&mut Cursor::new(bytes)).map_err(|e| e.to_string())?;
+pub fn detailed_disassemble(bytes: Vec<u8>, format: &BinaryFormat) -> Result<Vec<InspectionItem>, String> {
+    let bytes = binary_elf(bytes, format)?;
+    let elf = Elf::read(&mut Cursor::new(&bytes)).map_err(|e| e.to_string())?;
 
-    Ok(make_inspection_lines(&elf)
-        .into_iter()
-        .map(|line| match line {
-            InspectionLine::Instruction(inst) => InspectionItem::Instruction {
-                details: InstructionDetails {
-                    pc: inst.pc,
-                    instruction: inst.instruction,
-                    name: inst.name,
-                    parameters: inst.parameters
-                        .into_iter()
-                        .map(parameter_to_item)
-                        .collect()
+    // Names from the symbol table replace the generated labels.
+    let mut labels: HashMap<u32, Vec<String>> = HashMap::new();
+
+    for symbol in read_debug_info(&bytes).map(|info| info.symbols).unwrap_or_default() {
+        labels.entry(symbol.address).or_default().push(symbol.name)
+    }
+
+    let named = !labels.is_empty();
+    let mut result = vec![];
+
+    for line in make_inspection_lines(&elf) {
+        match line {
+            InspectionLine::Instruction(inst) => {
+                if let Some(names) = labels.remove(&inst.pc) {
+                    result.extend(names.into_iter().map(|name| InspectionItem::Label { name }))
                 }
-            },
-            InspectionLine::Blank => InspectionItem::Blank,
-            InspectionLine::Comment(value) => InspectionItem::Comment { message: value },
-            InspectionLine::Label(value) => InspectionItem::Label { name: value }
-        })
-        .collect())
+
+                result.push(InspectionItem::Instruction {
+                    details: InstructionDetails {
+                        pc: inst.pc,
+                        instruction: inst.instruction,
+                        name: inst.name,
+                        parameters: inst.parameters
+                            .into_iter()
+                            .map(parameter_to_item)
+                            .collect()
+                    }
+                })
+            }
+            InspectionLine::Blank => result.push(InspectionItem::Blank),
+            InspectionLine::Comment(value) => result.push(InspectionItem::Comment { message: value }),
+            InspectionLine::Label(_) if named => { }
+            InspectionLine::Label(value) => result.push(InspectionItem::Label { name: value })
+        }
+    }
+
+    Ok(result)
 }
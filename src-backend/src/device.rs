@@ -1,20 +1,63 @@
-use crate::keyboard::KeyboardState;
+use crate::display::FlushDisplayState;
+use crate::exception::Coprocessor0;
+use crate::layout::MemoryLayout;
+use crate::mmio::{MmioDevices, MmioHandler};
 use crate::syscall::SyscallState;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use titan::assembler::binary::Binary;
 use titan::cpu::{Memory, State};
 use titan::cpu::memory::{Mountable, Region};
+use titan::cpu::memory::section::SectionMemory;
+use titan::cpu::memory::watched::WatchedMemory;
 use titan::execution::Executor;
 use titan::execution::trackers::Tracker;
 
+const GLOBAL_AREA_SIZE: u32 = 0x8000;
+
 pub struct ExecutionState<Mem: Memory, Track: Tracker<Mem>> {
     pub debugger: Arc<Executor<Mem, Track>>,
-    pub keyboard: Arc<Mutex<KeyboardState>>,
+    pub devices: MmioDevices,
     pub delegate: Arc<Mutex<SyscallState>>,
+    pub cop0: Arc<Mutex<Coprocessor0>>,
     pub finished_pcs: Vec<u32>,
+    // Start of the device page, from the memory layout.
+    pub mmio: u32,
+}
+
+impl<Mem: Memory, Track: Tracker<Mem>> ExecutionState<Mem, Track> {
+    pub fn new(
+        debugger: Executor<Mem, Track>,
+        devices: MmioDevices,
+        mut delegate: SyscallState,
+        finished_pcs: Vec<u32>,
+        layout: &MemoryLayout,
+    ) -> ExecutionState<Mem, Track> {
+        devices.registry.set_clock(delegate.time_handler());
+        delegate.set_heap_start(layout.heap);
+
+        ExecutionState {
+            debugger: Arc::new(debugger),
+            devices,
+            delegate: Arc::new(Mutex::new(delegate)),
+            cop0: Arc::new(Mutex::new(Coprocessor0::new(layout.exception_handler))),
+            finished_pcs,
+            mmio: layout.mmio,
+        }
+    }
 }
 
-pub fn state_from_binary<Mem: Memory + Mountable>(binary: Binary, heap_size: u32, mut memory: Mem) -> State<Mem> {
+pub fn state_from_binary<Mem: Memory + Mountable>(
+    binary: Binary,
+    stack_size: u32,
+    layout: &MemoryLayout,
+    mut memory: Mem,
+) -> State<Mem> {
+    let program: Vec<(u32, u32)> = binary.regions
+        .iter()
+        .map(|region| (region.address, region_end(region.address, region.data.len())))
+        .collect();
+
     for region in binary.regions {
         let region = Region {
             start: region.address,
@@ -24,31 +67,124 @@ pub fn state_from_binary<Mem: Memory + Mountable>(binary: Binary, heap_size: u32
         memory.mount(region);
     }
 
-    // Keeping this around temporarily.
-    let heap_end = 0x80000000u32;
-
-    let heap = Region {
-        start: heap_end - heap_size,
-        data: vec![0; heap_size as usize],
-    };
-
-    memory.mount(heap);
+    mount_stack(&mut memory, stack_size, layout, &program);
 
     let mut state = State::new(binary.entry, memory);
 
-    state.registers.line[29] = heap_end - 4; // give some space
+    state.registers.line[29] = layout.stack_pointer;
 
     state
 }
 
-pub fn setup_state<Mem: Memory + Mountable>(state: &mut State<Mem>) {
-    let max_screen = 0x8000;
+// End of size bytes at start, regions running past the top of memory stop there.
+pub(crate) fn region_end(start: u32, size: usize) -> u32 {
+    u32::try_from(size)
+        .ok()
+        .and_then(|size| start.checked_add(size))
+        .unwrap_or(u32::MAX)
+}
+
+// Backs the stack with zeroed memory ending at the top of the layout's stack.
+// The compact layouts are small enough that the program can sit right under it, so the stack never covers it.
+pub fn mount_stack<Mem: Mountable>(memory: &mut Mem, stack_size: u32, layout: &MemoryLayout, program: &[(u32, u32)]) {
+    let end = layout.stack_end();
+
+    let start = program
+        .iter()
+        .filter(|(start, _)| *start < end)
+        .map(|(_, region_end)| *region_end)
+        .fold(end.saturating_sub(stack_size), u32::max)
+        .min(end);
+
+    let stack = Region {
+        start,
+        data: vec![0; (end - start) as usize],
+    };
+
+    memory.mount(stack);
+}
+
+// Zeroes the $gp area and memory for the display. Call before loading the program,
+// so a display placed over static data doesn't erase it.
+pub fn mount_display<Mem: Mountable>(memory: &mut Mem, display: &FlushDisplayState, layout: &MemoryLayout) {
+    let globals = global_area(layout);
+
+    let area = Region {
+        start: globals.start,
+        data: vec![0; globals.len()],
+    };
+
+    memory.mount(area);
+
+    let Some(size) = display.size() else { return };
+
+    if display.address.checked_add(size).is_none() {
+        return
+    }
+
     let screen = Region {
-        start: 0x10008000,
-        data: vec![0; max_screen],
+        start: display.address,
+        data: vec![0; size as usize],
     };
 
-    state.memory.mount(screen);
+    memory.mount(screen);
+}
+
+// MARS backs 32KB above $gp, the compact layouts put the heap or static data closer than that.
+fn global_area(layout: &MemoryLayout) -> Range<u32> {
+    let start = layout.global_pointer;
+
+    let end = [layout.text, layout.data, layout.heap, layout.kernel_text, layout.kernel_data]
+        .into_iter()
+        .filter(|address| *address > start)
+        .fold(start.saturating_add(GLOBAL_AREA_SIZE), u32::min);
+
+    start .. end
+}
+
+// Mounts the devices at the layout's device page, and puts the display and its palette under sections
+// that record which rows get written, so reads only go over rows that changed.
+// Call after loading the program, the sections keep what's there.
+pub fn mount_listeners(
+    memory: &mut SectionMemory<MmioHandler>,
+    devices: &MmioDevices,
+    display: &mut FlushDisplayState,
+    layout: &MemoryLayout,
+) {
+    let writes = display.watch(layout.mmio);
+    let device_selector = layout.mmio_selector();
+
+    display.hide(devices.registry.ranges_at(layout.mmio));
+
+    let mut selectors = writes.as_ref().map(|writes| writes.selectors()).unwrap_or_default();
+    selectors.push(device_selector);
+    selectors.sort_unstable();
+    selectors.dedup();
+
+    for selector in selectors {
+        let base = selector << 16;
+
+        let data = (0 .. 0x10000u32)
+            .map(|offset| memory.get(base | offset).unwrap_or(0))
+            .collect();
+
+        let registry = (selector == device_selector).then(|| devices.registry.clone());
+        let writes = writes.clone().filter(|writes| writes.selectors().contains(&selector));
+
+        memory.mount_listen(selector as usize, MmioHandler::new(registry, layout.mmio, base, data, writes));
+    }
+}
+
+// Time travel records every write, so the wrapper goes on once the program and display are in place.
+pub fn watch_state<Mem: Memory>(state: State<Mem>) -> State<WatchedMemory<Mem>> {
+    let State { registers, memory, .. } = state;
+
+    let mut result = State::new(registers.pc, WatchedMemory::new(memory));
+    result.registers = registers;
+
+    result
+}
 
-    state.registers.line[28] = 0x10008000
+pub fn setup_state<Mem: Memory + Mountable>(state: &mut State<Mem>, layout: &MemoryLayout) {
+    state.registers.line[28] = layout.global_pointer
 }
@@ -0,0 +1,307 @@
+use serde::Serialize;
+use titan::assembler::string::SourceError;
+use crate::build::assemble_text;
+use crate::logpoint::REGISTER_NAMES;
+
+// Errors collected past this are dropped, each one costs another assembly.
+pub const MAX_DIAGNOSTICS: usize = 32;
+
+// Instructions whose first operand is a destination register.
+const WRITES_FIRST: [&str; 36] = [
+    "add", "addi", "addu", "addiu", "sub", "subu", "and", "andi", "or", "ori",
+    "xor", "xori", "nor", "slt", "slti", "sltu", "sltiu", "sll", "srl", "sra",
+    "sllv", "srlv", "srav", "lw", "lh", "lhu", "lb", "lbu", "lui", "li",
+    "la", "move", "mfhi", "mflo", "mul", "neg",
+];
+
+#[derive(Clone, Copy, Serialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Zero based, column is a byte offset into the line.
+#[derive(Clone, Copy, Serialize)]
+pub struct SourcePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+// Replaces the diagnostic's range with replacement.
+#[derive(Clone, Serialize)]
+pub struct FixIt {
+    pub message: String,
+    pub replacement: String,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub path: Option<String>,
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+    pub fixes: Vec<FixIt>,
+}
+
+fn position(source: &str, mut index: usize) -> SourcePosition {
+    while !source.is_char_boundary(index) {
+        index -= 1
+    }
+
+    let before = &source[..index];
+    let line_start = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+
+    SourcePosition {
+        line: before.matches('\n').count(),
+        column: index - line_start,
+    }
+}
+
+fn is_token_end(c: u8) -> bool {
+    c.is_ascii_whitespace() || matches!(c, b',' | b'(' | b')' | b'#')
+}
+
+// The assembler only reports where an error starts, so it ends with the token there.
+fn token_end(source: &str, index: usize) -> usize {
+    source.as_bytes()[index..]
+        .iter()
+        .position(|c| is_token_end(*c))
+        .map(|length| index + length)
+        .unwrap_or(source.len())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, y) in b.iter().enumerate() {
+            let current = row[j + 1];
+
+            row[j + 1] = if x == *y {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+
+            previous = current;
+        }
+    }
+
+    row[b.len()]
+}
+
+fn is_register(token: &str) -> bool {
+    let Some(name) = token.strip_prefix('$') else { return false };
+
+    REGISTER_NAMES.contains(&name) || name.parse::<u8>().map(|n| n < 32).unwrap_or(false)
+}
+
+// Suggests the closest register name for a misspelled register.
+fn register_fixes(token: &str) -> Vec<FixIt> {
+    let Some(name) = token.strip_prefix('$') else { return vec![] };
+
+    if is_register(token) {
+        return vec![]
+    }
+
+    let mut candidates: Vec<(usize, &str)> = REGISTER_NAMES
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 1)
+        .collect();
+
+    candidates.sort_by_key(|(distance, _)| *distance);
+
+    candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| FixIt {
+            message: format!("Did you mean ${}?", candidate),
+            replacement: format!("${}", candidate),
+        })
+        .collect()
+}
+
+// Where the error starts in source, None if it has no location or it's in an included file.
+fn error_start(source: &str, error: &SourceError) -> Option<usize> {
+    error.start()
+        .filter(|location| location.id == 0)
+        .map(|location| location.index)
+        .filter(|index| *index <= source.len())
+}
+
+fn error_diagnostic(source: &str, path: Option<&str>, error: &SourceError) -> Diagnostic {
+    let index = error_start(source, error).unwrap_or(0);
+    let end = token_end(source, index);
+
+    Diagnostic {
+        severity: Severity::Error,
+        message: format!("{}", error),
+        path: path.map(|path| path.to_string()),
+        start: position(source, index),
+        end: position(source, end),
+        fixes: register_fixes(&source[index..end]),
+    }
+}
+
+fn is_label_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'$')
+}
+
+// Offset of the statement on line, after any labels in front of it.
+fn statement_start(line: &[u8]) -> usize {
+    let mut start = 0;
+
+    loop {
+        let word = start + line[start..].iter().take_while(|c| c.is_ascii_whitespace()).count();
+        let end = word + line[word..].iter().take_while(|c| is_label_char(**c)).count();
+
+        if end == word || line.get(end) != Some(&b':') {
+            return start
+        }
+
+        start = end + 1;
+    }
+}
+
+// Spaces out line_start to the end of its line, keeping every other offset the same.
+// Returns false if there was nothing left to remove.
+fn blank_line(source: &mut [u8], line_start: usize, keep_labels: bool) -> bool {
+    let line_end = source[line_start..]
+        .iter()
+        .position(|c| *c == b'\n')
+        .map(|length| line_start + length)
+        .unwrap_or(source.len());
+
+    let line = &mut source[line_start..line_end];
+    let start = if keep_labels { statement_start(line) } else { 0 };
+
+    let mut changed = false;
+
+    // Multibyte characters are spaced out byte by byte, so the text stays valid UTF-8.
+    for c in line[start..].iter_mut() {
+        if !c.is_ascii_whitespace() {
+            *c = b' ';
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+// The assembler stops at the first error. To report the rest, the failing statement is blanked
+// (keeping labels in front of it, so references to them still resolve) and the source is assembled again.
+// Errors in included files have no position here, so collecting stops at one.
+// Each error after the first costs a full assembly, so a source with many errors is assembled
+// up to MAX_DIAGNOSTICS times. The editor's syntax check runs this whenever typing pauses.
+pub fn collect_errors(source: &str, path: Option<&str>, first: &SourceError) -> Vec<Diagnostic> {
+    let mut start = error_start(source, first);
+    let mut result = vec![error_diagnostic(source, path, first)];
+    let mut blanked = vec![];
+
+    let mut text = source.as_bytes().to_vec();
+
+    while result.len() < MAX_DIAGNOSTICS {
+        let Some(index) = start else { break };
+
+        let line_start = text[..index]
+            .iter()
+            .rposition(|c| *c == b'\n')
+            .map(|x| x + 1)
+            .unwrap_or(0);
+
+        // Another error on a line with its statement gone comes from the labels, so they go too.
+        let keep_labels = !blanked.contains(&line_start);
+
+        if !blank_line(&mut text, line_start, keep_labels) {
+            break
+        }
+
+        blanked.push(line_start);
+
+        let Ok(current) = String::from_utf8(text.clone()) else { break };
+
+        let Err(error) = assemble_text(&current, path) else { break };
+
+        start = error_start(&current, &error);
+
+        if start.is_none() {
+            continue
+        }
+
+        let diagnostic = error_diagnostic(&current, path, &error);
+
+        // One error per line, the rest are usually knock-on errors from the first.
+        if !result.iter().any(|other| other.start.line == diagnostic.start.line) {
+            result.push(diagnostic)
+        }
+    }
+
+    result
+}
+
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+// Cheap checks on the source text for code that assembles but probably does not do what was meant.
+pub fn collect_warnings(source: &str, path: Option<&str>) -> Vec<Diagnostic> {
+    let mut result = vec![];
+
+    for (line, text) in source.split('\n').enumerate() {
+        let code = strip_comment(text);
+
+        // Skip a label, then the first word is the instruction.
+        let label = code.find(':').filter(|index| !code[..*index].contains('"'));
+        let body = label.map(|index| &code[index + 1..]).unwrap_or(code);
+        let body_start = code.len() - body.len();
+
+        let mut tokens = body
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
+            .filter(|token| !token.is_empty())
+            .map(|token| (token.as_ptr() as usize - body.as_ptr() as usize + body_start, token));
+
+        let Some((_, instruction)) = tokens.next() else { continue };
+
+        // Directives hold data and strings, not registers.
+        if instruction.starts_with('.') {
+            continue
+        }
+
+        let writes_first = WRITES_FIRST.contains(&instruction);
+
+        for (index, (column, token)) in tokens.enumerate() {
+            let message = match token {
+                "$at" | "$1" => "$at is used by pseudo-instructions and may be overwritten",
+                "$zero" | "$0" if index == 0 && writes_first => "Writing to $zero has no effect",
+                _ => continue
+            };
+
+            result.push(Diagnostic {
+                severity: Severity::Warning,
+                message: message.to_string(),
+                path: path.map(|path| path.to_string()),
+                start: SourcePosition { line, column },
+                end: SourcePosition { line, column: column + token.len() },
+                fixes: vec![],
+            });
+        }
+    }
+
+    result
+}
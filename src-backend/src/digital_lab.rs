@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use crate::exception::{INTERRUPT_KEYPAD, INTERRUPT_LAB_TIMER};
+use crate::mmio::{parse_input, MmioDevice};
+
+// Laid out like the MARS Digital Lab Sim.
+//   0xFFFF0010 right seven segment display (bit 0 is segment a, bit 7 is the dot)
+//   0xFFFF0011 left seven segment display
+//   0xFFFF0012 keypad row select (bits 0 to 3), bit 7 enables the keypad interrupt
+//   0xFFFF0013 bit 0 enables the timer interrupt
+//   0xFFFF0014 keypad scan, (column bit << 4) | row bit if the pressed key is in a selected row
+pub const DIGITAL_LAB_ADDRESS: u32 = 0xFFFF0010;
+pub const DIGITAL_LAB_END: u32 = 0xFFFF0018;
+const DIGITAL_LAB_RANGE: Range<u32> = DIGITAL_LAB_ADDRESS..DIGITAL_LAB_END;
+
+// Instructions between timer interrupts, same as MARS.
+pub const DIGITAL_LAB_TIMER_PERIOD: u32 = 30;
+
+const KEYPAD_INTERRUPT_ENABLE: u8 = 0x80;
+const KEYPAD_ROWS: u8 = 0x0F;
+
+#[derive(Clone, Serialize)]
+pub struct DigitalLabSnapshot {
+    pub left: u8,
+    pub right: u8,
+    pub pressed: Option<u8>,
+}
+
+#[derive(Default)]
+pub struct DigitalLabState {
+    left: u8,
+    right: u8,
+    row_select: u8,
+    timer_enabled: bool,
+    timer_count: u32,
+    pressed: Option<u8>,
+    // Interrupt lines raised but not delivered yet.
+    pending: u32,
+}
+
+// Key 0 to 15, None releases the pressed key.
+#[derive(Deserialize)]
+struct KeypadInput {
+    key: Option<u8>,
+}
+
+#[derive(Default)]
+pub struct DigitalLabHandler {
+    pub state: Arc<Mutex<DigitalLabState>>,
+}
+
+impl DigitalLabState {
+    // Key 0 to 15 (0x0 to 0xF), None releases the pressed key.
+    pub fn press_key(&mut self, key: Option<u8>) {
+        self.pressed = key.filter(|key| *key < 16);
+
+        if self.pressed.is_some() && self.row_select & KEYPAD_INTERRUPT_ENABLE != 0 {
+            self.pending |= INTERRUPT_KEYPAD
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        if !self.timer_enabled {
+            return
+        }
+
+        self.timer_count += cycles;
+
+        if self.timer_count >= DIGITAL_LAB_TIMER_PERIOD {
+            self.timer_count %= DIGITAL_LAB_TIMER_PERIOD;
+            self.pending |= INTERRUPT_LAB_TIMER
+        }
+    }
+
+    // Instructions until the timer fires next.
+    pub fn next_event(&self) -> Option<u32> {
+        Some(DIGITAL_LAB_TIMER_PERIOD - self.timer_count).filter(|_| self.timer_enabled)
+    }
+
+    pub fn interrupts(&self) -> u32 {
+        self.pending
+    }
+
+    // MARS raises each interrupt once, so delivered lines are cleared.
+    pub fn acknowledge(&mut self, lines: u32) {
+        self.pending &= !lines
+    }
+
+    pub fn snapshot(&self) -> DigitalLabSnapshot {
+        DigitalLabSnapshot {
+            left: self.left,
+            right: self.right,
+            pressed: self.pressed,
+        }
+    }
+
+    fn scan(&self) -> u8 {
+        let Some(key) = self.pressed else { return 0 };
+
+        let row = 1 << (key / 4);
+        let column = 1 << (key % 4);
+
+        if self.row_select & KEYPAD_ROWS & row != 0 {
+            (column << 4) | row
+        } else {
+            0
+        }
+    }
+}
+
+impl MmioDevice for DigitalLabHandler {
+    fn name(&self) -> &'static str {
+        "digital_lab"
+    }
+
+    fn ranges(&self) -> &[Range<u32>] {
+        std::slice::from_ref(&DIGITAL_LAB_RANGE)
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let state = self.state.lock().unwrap();
+
+        Ok(match address.wrapping_sub(DIGITAL_LAB_ADDRESS) {
+            0 => state.right,
+            1 => state.left,
+            2 => state.row_select,
+            3 => state.timer_enabled as u8,
+            4 => state.scan(),
+            5..=7 => 0,
+            _ => return Err(MemoryUnmapped(address))
+        })
+    }
+
+    fn write(&self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        match address.wrapping_sub(DIGITAL_LAB_ADDRESS) {
+            0 => state.right = value,
+            1 => state.left = value,
+            2 => state.row_select = value,
+            3 => {
+                state.timer_enabled = value & 1 != 0;
+                state.timer_count = 0;
+            }
+            // The scan register is read only.
+            4..=7 => {}
+            _ => return Err(MemoryUnmapped(address))
+        }
+
+        Ok(())
+    }
+
+    fn tick(&self, cycles: u32) {
+        self.state.lock().unwrap().tick(cycles)
+    }
+
+    fn next_event(&self) -> Option<u32> {
+        self.state.lock().unwrap().next_event()
+    }
+
+    fn lines(&self) -> u32 {
+        INTERRUPT_LAB_TIMER | INTERRUPT_KEYPAD
+    }
+
+    fn interrupts(&self) -> u32 {
+        self.state.lock().unwrap().interrupts()
+    }
+
+    fn acknowledge(&self, lines: u32) {
+        self.state.lock().unwrap().acknowledge(lines)
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self.state.lock().unwrap().snapshot()).ok()
+    }
+
+    fn input(&self, input: serde_json::Value) -> Result<(), String> {
+        let input: KeypadInput = parse_input(input)?;
+
+        self.state.lock().unwrap().press_key(input.key);
+
+        Ok(())
+    }
+}
@@ -1,57 +1,800 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use titan::cpu::Memory;
+use crate::layout::MemoryLayout;
+use crate::syscall::TimeHandler;
 
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum DisplayFormat {
+    // 0x00RRGGBB words, alpha is always 255.
+    #[default]
+    Rgb32,
+    // 0xAARRGGBB words.
+    Argb32,
+    // RRRRRGGGGGGBBBBB halfwords.
+    Rgb565,
+    // One byte per pixel indexing 256 0x00RRGGBB words at palette.
+    Indexed8 { palette: u32 },
+    // One byte per pixel, 0 is black and 255 is white.
+    Grayscale8,
+    // One bit per pixel, most significant bit first, each row starts on a new byte.
+    Monochrome,
+}
+
+impl DisplayFormat {
+    // Bytes of memory taken by a width x height display.
+    pub fn size(&self, width: u32, height: u32) -> Option<u32> {
+        match self {
+            DisplayFormat::Rgb32 | DisplayFormat::Argb32 => width.checked_mul(height)?.checked_mul(4),
+            DisplayFormat::Rgb565 => width.checked_mul(height)?.checked_mul(2),
+            DisplayFormat::Indexed8 { .. } | DisplayFormat::Grayscale8 => width.checked_mul(height),
+            DisplayFormat::Monochrome => (width / 8 + (width % 8 != 0) as u32).checked_mul(height),
+        }
+    }
+}
+
+// Base addresses offered by the MARS bitmap display, resolved against the memory layout.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayBase {
+    GlobalPointer,
+    StaticData,
+    Heap,
+    // The device page. Device registers there are drawn black and never read, see FlushDisplayState::hide.
+    Mmio,
+}
+
+impl DisplayBase {
+    pub fn address_in(&self, layout: &MemoryLayout) -> u32 {
+        match self {
+            DisplayBase::GlobalPointer => layout.global_pointer,
+            DisplayBase::StaticData => layout.data,
+            DisplayBase::Heap => layout.heap,
+            DisplayBase::Mmio => layout.mmio,
+        }
+    }
+}
+
+// Optional display settings, missing fields take their defaults.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayOptions {
+    pub format: DisplayFormat,
+    // Overrides the address when set.
+    pub base: Option<DisplayBase>,
+    // Unit width and height in pixels.
+    pub unit: Option<(u32, u32)>,
+    // Only frames the program presents (syscall 60) are shown.
+    pub double_buffer: bool,
+}
+
+// width and height count units (memory cells), each drawn as unit_width x unit_height pixels.
 #[derive(Clone, Serialize)]
 pub struct FlushDisplayState {
     pub address: u32,
     pub width: u32,
     pub height: u32,
+    pub unit_width: u32,
+    pub unit_height: u32,
+    pub format: DisplayFormat,
+    pub double_buffer: bool,
     pub data: Option<Vec<u8>>, // flush should impact this
+    #[serde(skip)]
+    pub recorder: Option<DisplayRecorder>,
+    // Rows that changed since the last take_delta.
+    #[serde(skip)]
+    dirty: Vec<bool>,
+    // Rows written since they were last read, None if the display memory isn't watched.
+    #[serde(skip)]
+    writes: Option<DisplayWrites>,
+    // Device registers, reading them could change the device.
+    #[serde(skip)]
+    hidden: Vec<Range<u32>>,
 }
 
 impl Default for FlushDisplayState {
     fn default() -> FlushDisplayState {
         FlushDisplayState {
-            address: 0x10008000,
+            address: MemoryLayout::default().global_pointer,
             width: 64,
             height: 64,
+            unit_width: 1,
+            unit_height: 1,
+            format: DisplayFormat::default(),
+            double_buffer: false,
             data: None,
+            recorder: None,
+            dirty: vec![],
+            writes: None,
+            hidden: vec![],
         }
     }
 }
 
 impl FlushDisplayState {
+    pub fn new(
+        address: u32,
+        width: u32,
+        height: u32,
+        options: DisplayOptions,
+        layout: &MemoryLayout,
+    ) -> FlushDisplayState {
+        let (unit_width, unit_height) = options.unit.unwrap_or((1, 1));
+
+        FlushDisplayState {
+            address: options.base.map(|base| base.address_in(layout)).unwrap_or(address),
+            width,
+            height,
+            unit_width: unit_width.max(1),
+            unit_height: unit_height.max(1),
+            format: options.format,
+            double_buffer: options.double_buffer,
+            data: None,
+            recorder: None,
+            dirty: vec![],
+            writes: None,
+            hidden: vec![],
+        }
+    }
+
+    // Bytes of memory the display reads from.
+    pub fn size(&self) -> Option<u32> {
+        self.format.size(self.width, self.height)
+    }
+
+    // Size of the drawn display in pixels, each unit is unit_width x unit_height of them.
+    pub fn pixel_size(&self) -> (u32, u32) {
+        (self.width.saturating_mul(self.unit_width), self.height.saturating_mul(self.unit_height))
+    }
+
+    // Whether a front end read is for this display. Other reads get unscaled memory.
+    pub fn matches(&self, address: u32, width: u32, height: u32) -> bool {
+        self.address == address && self.width == width && self.height == height
+    }
+
+    // Turns a frame read in units into pixels.
+    pub fn scale(&self, frame: &[u8]) -> Vec<u8> {
+        scale_frame(frame, self.width, self.unit_width, self.unit_height)
+    }
+
+    // The last flushed or presented frame, in pixels.
+    pub fn last_frame(&self) -> DisplayFrame {
+        let (width, height) = self.pixel_size();
+
+        DisplayFrame {
+            address: self.address,
+            width,
+            height,
+            data: self.data.as_ref().map(|data| self.scale(data)),
+        }
+    }
+
+    // Called between batches, double buffered displays keep their last presented frame.
     pub fn flush<Mem: Memory>(&mut self, memory: &mut Mem) {
-        self.data = read_display(self.address, self.width, self.height, memory);
+        if !self.double_buffer {
+            self.present(memory)
+        }
+    }
+
+    // Reads the rows written since the last read, or every row if the display memory isn't watched.
+    pub fn present<Mem: Memory>(&mut self, memory: &mut Mem) {
+        let pixels = (self.width as usize) * (self.height as usize);
+
+        let previous = self.data.take().filter(|frame| frame.len() == pixels * 4);
+        let written = self.writes.as_ref().map(|writes| writes.take());
+
+        let (mut frame, stale) = match previous {
+            Some(frame) => (frame, written.unwrap_or_else(|| vec![true; self.height as usize])),
+            None => {
+                // Nothing to compare against, so every row counts as changed.
+                self.dirty = vec![true; self.height as usize];
+
+                (vec![0; pixels * 4], vec![true; self.height as usize])
+            }
+        };
+
+        let Some(changed) = read_rows(self.address, self.width, self.format, &self.hidden, &stale, &mut frame, memory) else {
+            self.dirty = vec![true; self.height as usize];
+
+            return
+        };
+
+        self.dirty.resize(self.height as usize, true);
+
+        for (dirty, changed) in self.dirty.iter_mut().zip(&changed) {
+            *dirty |= *changed
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            recorder.capture(&frame)
+        }
+
+        self.data = Some(frame)
+    }
+
+    // Skips these ranges when reading the display, they're drawn black.
+    pub fn hide(&mut self, ranges: Vec<Range<u32>>) {
+        self.hidden = ranges
+    }
+
+    // Starts recording which rows of the display memory are written.
+    // The returned tracker goes to the memory sections under the display, see device::watch_display.
+    pub fn watch(&mut self, mmio: u32) -> Option<DisplayWrites> {
+        let writes = DisplayWrites::new(self, mmio)?;
+
+        self.writes = Some(writes.clone());
+
+        Some(writes)
+    }
+
+    // Changed rows since the last call, in pixels, encoded as a list of spans:
+    //   start row (u32 LE), row count (u32 LE), then row count * pixel width RGBA pixels.
+    pub fn take_delta(&mut self) -> Vec<u8> {
+        let mut result = vec![];
+
+        let Some(data) = &self.data else { return result };
+
+        let row_bytes = (self.width as usize) * 4;
+        let mut row = 0;
+
+        while row < self.dirty.len() {
+            if !self.dirty[row] {
+                row += 1;
+
+                continue
+            }
+
+            let start = row;
+
+            while row < self.dirty.len() && self.dirty[row] {
+                self.dirty[row] = false;
+                row += 1;
+            }
+
+            let Some(rows) = data.get(start * row_bytes .. row * row_bytes) else { break };
+
+            result.extend_from_slice(&(start as u32 * self.unit_height).to_le_bytes());
+            result.extend_from_slice(&((row - start) as u32 * self.unit_height).to_le_bytes());
+            result.extend_from_slice(&scale_frame(rows, self.width, self.unit_width, self.unit_height));
+        }
+
+        result
+    }
+
+    // Starts capturing every frame that changes, replacing any previous recording.
+    // Frames are timed with clock, so the speed doesn't depend on how often the display is read.
+    pub fn start_recording(&mut self, frame_delay: u32, clock: Arc<dyn TimeHandler + Send + Sync>) {
+        self.recorder = Some(DisplayRecorder::new(
+            self.width, self.height, (self.unit_width, self.unit_height), frame_delay, clock
+        ))
+    }
+
+    pub fn stop_recording(&mut self) -> Option<DisplayRecorder> {
+        let mut recorder = self.recorder.take()?;
+
+        recorder.finish();
+
+        Some(recorder)
+    }
+
+    // Carries an active recording over from the display this one replaces.
+    // A recording can't change size, so one for a different size is stopped and returned instead.
+    pub fn take_recording(&mut self, previous: &mut FlushDisplayState) -> Option<DisplayRecorder> {
+        let same_size = (self.width, self.height, self.unit_width, self.unit_height)
+            == (previous.width, previous.height, previous.unit_width, previous.unit_height);
+
+        if same_size {
+            self.recorder = previous.recorder.take();
+
+            None
+        } else {
+            previous.stop_recording()
+        }
+    }
+
+    // The whole current frame in pixels, once it's sent every row is up to date.
+    // Double buffered displays are blank until the first present.
+    pub fn take_frame(&mut self) -> Option<Vec<u8>> {
+        let frame = match &self.data {
+            Some(data) => self.scale(data),
+            None if self.double_buffer => {
+                let (width, height) = self.pixel_size();
+
+                vec![0; (width as usize) * (height as usize) * 4]
+            }
+            None => return None,
+        };
+
+        self.dirty.iter_mut().for_each(|dirty| *dirty = false);
+
+        Some(frame)
     }
 }
 
 pub type FlushDisplayBody = Arc<Mutex<FlushDisplayState>>;
 
+// Rows of the display written since they were last read.
+// Shared between the display state and the memory sections under the display, marking is lock free.
+#[derive(Clone)]
+pub struct DisplayWrites {
+    range: Range<u32>,
+    // Bytes of memory per row.
+    stride: u32,
+    palette: Option<Range<u32>>,
+    rows: Arc<Vec<AtomicBool>>,
+    // Every row has to be read again, after a palette change.
+    all: Arc<AtomicBool>,
+}
+
+impl DisplayWrites {
+    // None if the display overlaps the device page at mmio, reading it there would reach the devices.
+    fn new(display: &FlushDisplayState, mmio: u32) -> Option<DisplayWrites> {
+        let range = display.address .. display.address.checked_add(display.size()?)?;
+        let stride = display.format.size(display.width, 1)?;
+
+        let palette = match display.format {
+            DisplayFormat::Indexed8 { palette } => Some(palette .. palette.checked_add(256 * 4)?),
+            _ => None,
+        };
+
+        let devices = mmio .. mmio.saturating_add(0x10000);
+        let outside = |range: &Range<u32>| range.end <= devices.start || range.start >= devices.end;
+
+        if stride == 0 || !outside(&range) || !palette.as_ref().map(outside).unwrap_or(true) {
+            return None
+        }
+
+        Some(DisplayWrites {
+            range,
+            stride,
+            palette,
+            rows: Arc::new((0 .. display.height).map(|_| AtomicBool::new(false)).collect()),
+            all: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    // 64KB sections holding the display or its palette.
+    pub fn selectors(&self) -> Vec<u32> {
+        let mut result: Vec<u32> = [Some(&self.range), self.palette.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter(|range| !range.is_empty())
+            .flat_map(|range| (range.start >> 16) ..= ((range.end - 1) >> 16))
+            .collect();
+
+        result.sort_unstable();
+        result.dedup();
+
+        result
+    }
+
+    pub fn mark(&self, address: u32) {
+        if self.range.contains(&address) {
+            let row = (address - self.range.start) / self.stride;
+
+            if let Some(row) = self.rows.get(row as usize) {
+                row.store(true, Ordering::Relaxed)
+            }
+        } else if self.palette.as_ref().map(|palette| palette.contains(&address)).unwrap_or(false) {
+            self.all.store(true, Ordering::Relaxed)
+        }
+    }
+
+    // Rows written since the last call.
+    fn take(&self) -> Vec<bool> {
+        let all = self.all.swap(false, Ordering::Relaxed);
+
+        self.rows
+            .iter()
+            .map(|row| row.swap(false, Ordering::Relaxed) || all)
+            .collect()
+    }
+}
+
+// What the front end draws when nothing is running.
+#[derive(Clone, Serialize)]
+pub struct DisplayFrame {
+    pub address: u32,
+    // In pixels.
+    pub width: u32,
+    pub height: u32,
+    pub data: Option<Vec<u8>>,
+}
+
+fn read_u16<Mem: Memory>(address: u32, memory: &Mem) -> Option<u16> {
+    // Assuming little endian, same as get_u32.
+    let low = memory.get(address).ok()? as u16;
+    let high = memory.get(address.wrapping_add(1)).ok()? as u16;
+
+    Some(low | (high << 8))
+}
+
+fn rgb_pixel(pixel: u32) -> [u8; 4] {
+    [
+        (pixel.wrapping_shr(16) & 0xFF) as u8,
+        (pixel.wrapping_shr(8) & 0xFF) as u8,
+        (pixel & 0xFF) as u8,
+        255,
+    ]
+}
+
 // NOT a tauri command.
+// Returns RGBA bytes, row by row. Pixels touching hidden ranges are black.
 pub fn read_display<Mem: Memory>(
     address: u32,
     width: u32,
     height: u32,
+    format: DisplayFormat,
+    hidden: &[Range<u32>],
     memory: &mut Mem,
 ) -> Option<Vec<u8>> {
     let pixels = width.checked_mul(height)?;
 
-    let mut result = vec![0u8; (pixels * 4) as usize];
+    let mut result = vec![0u8; (pixels as usize) * 4];
 
-    for i in 0..pixels {
-        let point = address.wrapping_add(i.wrapping_mul(4));
+    read_rows(address, width, format, hidden, &vec![true; height as usize], &mut result, memory)?;
 
-        let pixel = memory.get_u32(point).ok()?;
+    Some(result)
+}
 
-        // Assuming little endian: 0xAARRGGBB -> [BB, GG, RR, AA] -> want [RR, GG, BB, AA]
-        let start = (i as usize) * 4;
-        result[start] = (pixel.wrapping_shr(16) & 0xFF) as u8;
-        result[start + 1] = (pixel.wrapping_shr(8) & 0xFF) as u8;
-        result[start + 2] = (pixel.wrapping_shr(0) & 0xFF) as u8;
-        result[start + 3] = 255;
+// Reads the rows marked stale into frame, which holds the whole display as RGBA.
+// Returns which rows came out different.
+fn read_rows<Mem: Memory>(
+    address: u32,
+    width: u32,
+    format: DisplayFormat,
+    hidden: &[Range<u32>],
+    stale: &[bool],
+    frame: &mut [u8],
+    memory: &mut Mem,
+) -> Option<Vec<bool>> {
+    let mut changed = vec![false; stale.len()];
+
+    if !stale.contains(&true) {
+        return Some(changed)
     }
 
-    Some(result)
+    let palette = match format {
+        DisplayFormat::Indexed8 { palette } => (0..256u32)
+            .map(|i| {
+                let entry = palette.wrapping_add(i * 4);
+
+                if is_hidden(hidden, entry, 4) { Some(0) } else { memory.get_u32(entry).ok() }
+            })
+            .collect::<Option<Vec<u32>>>()?,
+        _ => vec![],
+    };
+
+    let row_bytes = (width as usize) * 4;
+    let mut row = vec![0u8; row_bytes];
+
+    for (y, stale) in stale.iter().enumerate() {
+        if !*stale {
+            continue
+        }
+
+        for x in 0..width {
+            let rgba = match pixel_bytes(address, width, x, y as u32, format) {
+                (start, size) if is_hidden(hidden, start, size) => [0, 0, 0, 255],
+                _ => read_pixel(address, width, x, y as u32, format, &palette, memory)?,
+            };
+
+            let start = (x as usize) * 4;
+            row[start..start + 4].copy_from_slice(&rgba);
+        }
+
+        let target = frame.get_mut(y * row_bytes .. (y + 1) * row_bytes)?;
+
+        if target != row.as_slice() {
+            target.copy_from_slice(&row);
+            changed[y] = true;
+        }
+    }
+
+    Some(changed)
+}
+
+// Whether any of the size bytes at start fall in a hidden range.
+fn is_hidden(hidden: &[Range<u32>], start: u32, size: u32) -> bool {
+    let end = start.saturating_add(size);
+
+    hidden.iter().any(|range| start < range.end && range.start < end)
+}
+
+// Address and size of the memory read_pixel reads for a pixel.
+fn pixel_bytes(address: u32, width: u32, x: u32, y: u32, format: DisplayFormat) -> (u32, u32) {
+    let i = y.wrapping_mul(width).wrapping_add(x);
+
+    match format {
+        DisplayFormat::Rgb32 | DisplayFormat::Argb32 => (address.wrapping_add(i.wrapping_mul(4)), 4),
+        DisplayFormat::Rgb565 => (address.wrapping_add(i.wrapping_mul(2)), 2),
+        DisplayFormat::Indexed8 { .. } | DisplayFormat::Grayscale8 => (address.wrapping_add(i), 1),
+        DisplayFormat::Monochrome => {
+            let row_bytes = width / 8 + (width % 8 != 0) as u32;
+
+            (address.wrapping_add(y * row_bytes + x / 8), 1)
+        }
+    }
+}
+
+fn read_pixel<Mem: Memory>(
+    address: u32,
+    width: u32,
+    x: u32,
+    y: u32,
+    format: DisplayFormat,
+    palette: &[u32],
+    memory: &mut Mem,
+) -> Option<[u8; 4]> {
+    let i = y.wrapping_mul(width).wrapping_add(x);
+
+    Some(match format {
+        DisplayFormat::Rgb32 => {
+            let pixel = memory.get_u32(address.wrapping_add(i.wrapping_mul(4))).ok()?;
+
+            rgb_pixel(pixel)
+        }
+        DisplayFormat::Argb32 => {
+            let pixel = memory.get_u32(address.wrapping_add(i.wrapping_mul(4))).ok()?;
+
+            let [r, g, b, _] = rgb_pixel(pixel);
+
+            [r, g, b, (pixel.wrapping_shr(24) & 0xFF) as u8]
+        }
+        DisplayFormat::Rgb565 => {
+            let pixel = read_u16(address.wrapping_add(i.wrapping_mul(2)), memory)?;
+
+            // Scale each channel up to 8 bits, repeating the high bits into the low bits.
+            let r = ((pixel >> 11) & 0x1F) as u8;
+            let g = ((pixel >> 5) & 0x3F) as u8;
+            let b = (pixel & 0x1F) as u8;
+
+            [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+        }
+        DisplayFormat::Indexed8 { .. } => {
+            let index = memory.get(address.wrapping_add(i)).ok()?;
+
+            rgb_pixel(palette[index as usize])
+        }
+        DisplayFormat::Grayscale8 => {
+            let value = memory.get(address.wrapping_add(i)).ok()?;
+
+            [value, value, value, 255]
+        }
+        DisplayFormat::Monochrome => {
+            let row_bytes = width / 8 + (width % 8 != 0) as u32;
+            let byte = memory.get(address.wrapping_add(y * row_bytes + x / 8)).ok()?;
+
+            let value = if byte & (0x80 >> (x % 8)) != 0 { 255 } else { 0 };
+
+            [value, value, value, 255]
+        }
+    })
+}
+
+// Centiseconds each captured frame is shown for in a GIF, when there's no clock to time them.
+pub const DEFAULT_FRAME_DELAY: u32 = 4;
+
+// Recordings stop taking frames once they hold this much frame data.
+pub const MAX_RECORDING_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Clone)]
+struct RecordedFrame {
+    data: Vec<u8>,
+    // Since the recording started.
+    at: Option<Duration>,
+}
+
+// Frames captured from the display, identical consecutive frames are stored once.
+#[derive(Clone)]
+pub struct DisplayRecorder {
+    width: u32,
+    height: u32,
+    unit_width: u32,
+    unit_height: u32,
+    frame_delay: u32,
+    clock: Arc<dyn TimeHandler + Send + Sync>,
+    start: Option<Duration>,
+    end: Option<Duration>,
+    bytes: usize,
+    // Frames were dropped after hitting MAX_RECORDING_BYTES.
+    truncated: bool,
+    frames: Vec<RecordedFrame>,
+}
+
+// Reported when a recording stops.
+#[derive(Clone, Serialize)]
+pub struct RecordingSummary {
+    pub frames: usize,
+    pub truncated: bool,
+}
+
+impl DisplayRecorder {
+    pub fn new(
+        width: u32,
+        height: u32,
+        (unit_width, unit_height): (u32, u32),
+        frame_delay: u32,
+        clock: Arc<dyn TimeHandler + Send + Sync>,
+    ) -> DisplayRecorder {
+        DisplayRecorder {
+            width,
+            height,
+            unit_width: unit_width.max(1),
+            unit_height: unit_height.max(1),
+            frame_delay: frame_delay.max(1),
+            start: clock.time(),
+            clock,
+            end: None,
+            bytes: 0,
+            truncated: false,
+            frames: vec![],
+        }
+    }
+
+    fn elapsed(&self) -> Option<Duration> {
+        Some(self.clock.time()?.saturating_sub(self.start?))
+    }
+
+    pub fn capture(&mut self, data: &[u8]) {
+        if self.end.is_some() || data.len() != (self.width as usize) * (self.height as usize) * 4 {
+            return
+        }
+
+        if self.frames.last().map(|last| last.data == data).unwrap_or(false) {
+            return
+        }
+
+        if self.bytes + data.len() > MAX_RECORDING_BYTES {
+            self.truncated = true;
+
+            return
+        }
+
+        self.bytes += data.len();
+
+        self.frames.push(RecordedFrame { data: data.to_vec(), at: self.elapsed() })
+    }
+
+    // The last frame is shown until now.
+    pub fn finish(&mut self) {
+        if self.end.is_none() {
+            self.end = self.elapsed()
+        }
+    }
+
+    pub fn summary(&self) -> RecordingSummary {
+        RecordingSummary { frames: self.len(), truncated: self.truncated }
+    }
+
+    // Number of distinct frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Centiseconds each frame is shown for, rounded against the start so errors don't add up.
+    fn delays(&self) -> Vec<u32> {
+        let centis = |time: Option<Duration>| time.map(|time| (time.as_millis() / 10) as u32);
+
+        self.frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let next = self.frames.get(index + 1).map(|next| next.at).unwrap_or(self.end);
+
+                match (centis(frame.at), centis(next)) {
+                    (Some(at), Some(next)) => next.saturating_sub(at).max(1),
+                    _ => self.frame_delay,
+                }
+            })
+            .collect()
+    }
+
+    fn scaled_size(&self) -> (u32, u32) {
+        (self.width * self.unit_width, self.height * self.unit_height)
+    }
+
+    pub fn encode_gif(&self) -> Result<Vec<u8>, String> {
+        let (width, height) = self.scaled_size();
+
+        let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(format!("Display is too large for a GIF ({}x{})", width, height))
+        };
+
+        let mut result = vec![];
+
+        {
+            let mut encoder = gif::Encoder::new(&mut result, gif_width, gif_height, &[])
+                .map_err(|error| error.to_string())?;
+
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| error.to_string())?;
+
+            for (frame, delay) in self.frames.iter().zip(self.delays()) {
+                let mut pixels = scale_frame(&frame.data, self.width, self.unit_width, self.unit_height);
+
+                let mut output = gif::Frame::from_rgba_speed(gif_width, gif_height, &mut pixels, 10);
+                output.delay = delay.min(u16::MAX as u32) as u16;
+
+                encoder.write_frame(&output).map_err(|error| error.to_string())?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    // One PNG per distinct frame, in order.
+    pub fn encode_pngs(&self) -> Result<Vec<Vec<u8>>, String> {
+        let (width, height) = self.scaled_size();
+
+        self.frames
+            .iter()
+            .map(|frame| {
+                let pixels = scale_frame(&frame.data, self.width, self.unit_width, self.unit_height);
+
+                encode_png(width, height, &pixels)
+            })
+            .collect()
+    }
+}
+
+// Repeats each pixel into a unit_width x unit_height block.
+fn scale_frame(data: &[u8], width: u32, unit_width: u32, unit_height: u32) -> Vec<u8> {
+    if unit_width == 1 && unit_height == 1 {
+        return data.to_vec()
+    }
+
+    let row_bytes = (width as usize) * 4;
+    let mut result = Vec::with_capacity(data.len() * (unit_width * unit_height) as usize);
+
+    for row in data.chunks(row_bytes) {
+        let mut scaled = Vec::with_capacity(row.len() * unit_width as usize);
+
+        for pixel in row.chunks(4) {
+            for _ in 0 .. unit_width {
+                scaled.extend_from_slice(pixel);
+            }
+        }
+
+        for _ in 0 .. unit_height {
+            result.extend_from_slice(&scaled);
+        }
+    }
+
+    result
+}
+
+// Takes RGBA bytes, like the ones returned by read_display.
+pub fn encode_png(width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut result = vec![];
+
+    {
+        let mut encoder = png::Encoder::new(&mut result, width, height);
+
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+
+        writer.write_image_data(data).map_err(|error| error.to_string())?;
+    }
+
+    Ok(result)
+}
+
+// Headless snapshot of the display, handy for comparing frames in tests.
+pub fn dump_display_png<Mem: Memory>(display: &FlushDisplayState, memory: &mut Mem) -> Result<Vec<u8>, String> {
+    let data = read_display(display.address, display.width, display.height, display.format, &display.hidden, memory)
+        .ok_or_else(|| "Display memory is not mapped".to_string())?;
+
+    let (width, height) = display.pixel_size();
+
+    encode_png(width, height, &display.scale(&data))
 }
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use crate::diagnostics::SourcePosition;
+use crate::symbols::{Symbol, SymbolSection};
+
+// Symbols and source lines carried by exported ELF files.
+// titan only writes program headers, so these sections are appended to its output:
+//   .text/.data     one per program header, so symbols have a section to point at
+//   .symtab/.strtab the labels, locals first as ELF requires
+//   .saturn.lines   (address, line) word pairs sorted by address, lines are zero based
+//   .saturn.source  the assembled source, which .saturn.lines refers to, only if the export asked for it
+//   .shstrtab       section names
+pub const LINES_SECTION: &str = ".saturn.lines";
+pub const SOURCE_SECTION: &str = ".saturn.source";
+
+const ELF_HEADER_SIZE: usize = 0x34;
+const PROGRAM_HEADER_SIZE: usize = 0x20;
+const SECTION_HEADER_SIZE: usize = 0x28;
+const SYMBOL_SIZE: usize = 0x10;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+
+const SHF_WRITE: u32 = 0x1;
+const SHF_ALLOC: u32 = 0x2;
+const SHF_EXECINSTR: u32 = 0x4;
+
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xFFF1;
+
+pub struct ElfDebugInfo {
+    pub symbols: Vec<Symbol>,
+    // (address, line) pairs, sorted by address.
+    pub lines: Vec<(u32, u32)>,
+    pub source: Option<String>,
+}
+
+impl ElfDebugInfo {
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    // Line of the instruction at address, if it came from the source.
+    pub fn line_at(&self, address: u32) -> Option<u32> {
+        self.lines
+            .binary_search_by_key(&address, |(pc, _)| *pc)
+            .ok()
+            .map(|index| self.lines[index].1)
+    }
+
+    // Every pc of each source line, like AssemblerResult breakpoints.
+    pub fn line_pcs(&self) -> Vec<(usize, Vec<u32>)> {
+        let mut result: HashMap<usize, Vec<u32>> = HashMap::new();
+
+        for (pc, line) in &self.lines {
+            result.entry(*line as usize).or_default().push(*pc)
+        }
+
+        let mut result: Vec<_> = result.into_iter().collect();
+        result.sort_by_key(|(line, _)| *line);
+
+        result
+    }
+}
+
+// Reads and writes words in the file's own byte order.
+#[derive(Copy, Clone)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(&self, bytes: &[u8], at: usize) -> Option<u16> {
+        let value: [u8; 2] = bytes.get(at..at + 2)?.try_into().ok()?;
+
+        Some(if self.big { u16::from_be_bytes(value) } else { u16::from_le_bytes(value) })
+    }
+
+    fn u32(&self, bytes: &[u8], at: usize) -> Option<u32> {
+        let value: [u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+
+        Some(if self.big { u32::from_be_bytes(value) } else { u32::from_le_bytes(value) })
+    }
+
+    fn put_u16(&self, out: &mut Vec<u8>, value: u16) {
+        out.extend(if self.big { value.to_be_bytes() } else { value.to_le_bytes() })
+    }
+
+    fn put_u32(&self, out: &mut Vec<u8>, value: u32) {
+        out.extend(if self.big { value.to_be_bytes() } else { value.to_le_bytes() })
+    }
+
+    fn set_u16(&self, out: &mut [u8], at: usize, value: u16) {
+        let value = if self.big { value.to_be_bytes() } else { value.to_le_bytes() };
+
+        out[at..at + 2].copy_from_slice(&value)
+    }
+
+    fn set_u32(&self, out: &mut [u8], at: usize, value: u32) {
+        let value = if self.big { value.to_be_bytes() } else { value.to_le_bytes() };
+
+        out[at..at + 4].copy_from_slice(&value)
+    }
+}
+
+// Only 32 bit ELF files are understood.
+fn endian(bytes: &[u8]) -> Option<Endian> {
+    if bytes.get(0..4)? != b"\x7FELF" || *bytes.get(4)? != 1 {
+        return None
+    }
+
+    Some(Endian { big: *bytes.get(5)? == 2 })
+}
+
+struct StringTable {
+    data: Vec<u8>,
+}
+
+impl StringTable {
+    fn new() -> StringTable {
+        StringTable { data: vec![0] }
+    }
+
+    fn add(&mut self, value: &str) -> u32 {
+        let offset = self.data.len() as u32;
+
+        self.data.extend(value.as_bytes());
+        self.data.push(0);
+
+        offset
+    }
+}
+
+fn read_string(table: &[u8], offset: usize) -> Option<String> {
+    let tail = table.get(offset..)?;
+    let end = tail.iter().position(|byte| *byte == 0)?;
+
+    Some(String::from_utf8_lossy(&tail[..end]).to_string())
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u32,
+    address: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+    info: u32,
+    align: u32,
+    entry_size: u32,
+}
+
+impl SectionHeader {
+    fn write(&self, endian: Endian, out: &mut Vec<u8>) {
+        for value in [
+            self.name, self.kind, self.flags, self.address, self.offset,
+            self.size, self.link, self.info, self.align, self.entry_size,
+        ] {
+            endian.put_u32(out, value)
+        }
+    }
+
+    fn read(endian: Endian, bytes: &[u8], at: usize) -> Option<SectionHeader> {
+        let word = |index: usize| endian.u32(bytes, at + index * 4);
+
+        Some(SectionHeader {
+            name: word(0)?,
+            kind: word(1)?,
+            flags: word(2)?,
+            address: word(3)?,
+            offset: word(4)?,
+            size: word(5)?,
+            link: word(6)?,
+            info: word(7)?,
+            align: word(8)?,
+            entry_size: word(9)?,
+        })
+    }
+
+    fn data<'a>(&self, bytes: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset as usize;
+
+        bytes.get(start..start.checked_add(self.size as usize)?)
+    }
+}
+
+fn align(out: &mut Vec<u8>) {
+    while out.len() % 4 != 0 {
+        out.push(0)
+    }
+}
+
+// Appends the debug sections to an ELF written by titan. Returns the file unchanged if it can't be read.
+pub fn append_debug_info(mut bytes: Vec<u8>, info: &ElfDebugInfo) -> Vec<u8> {
+    let Some(endian) = endian(&bytes) else { return bytes };
+
+    let (Some(program_offset), Some(program_count)) = (endian.u32(&bytes, 0x1C), endian.u16(&bytes, 0x2C)) else {
+        return bytes
+    };
+
+    let mut names = StringTable::new();
+    let mut headers = vec![SectionHeader {
+        name: 0, kind: 0, flags: 0, address: 0, offset: 0, size: 0, link: 0, info: 0, align: 0, entry_size: 0,
+    }];
+
+    // Program sections, symbols refer to these by index.
+    let mut text_count = 0;
+    let mut data_count = 0;
+
+    for index in 0..program_count as usize {
+        let at = program_offset as usize + index * PROGRAM_HEADER_SIZE;
+        let word = |offset: usize| endian.u32(&bytes, at + offset);
+
+        let (Some(offset), Some(address), Some(size), Some(flags)) = (word(4), word(8), word(16), word(24)) else {
+            return bytes
+        };
+
+        let executable = flags & PF_X != 0;
+        let (base, count) = if executable { (".text", &mut text_count) } else { (".data", &mut data_count) };
+
+        let name = if *count == 0 { base.to_string() } else { format!("{}.{}", base, count) };
+        *count += 1;
+
+        headers.push(SectionHeader {
+            name: names.add(&name),
+            kind: SHT_PROGBITS,
+            flags: SHF_ALLOC
+                | if executable { SHF_EXECINSTR } else { 0 }
+                | if flags & PF_W != 0 { SHF_WRITE } else { 0 },
+            address,
+            offset,
+            size,
+            link: 0,
+            info: 0,
+            align: 4,
+            entry_size: 0,
+        })
+    }
+
+    let section_of = |address: u32| -> u16 {
+        headers
+            .iter()
+            .position(|header| header.kind == SHT_PROGBITS
+                && header.address <= address && address - header.address <= header.size)
+            .map(|index| index as u16)
+            .unwrap_or(SHN_ABS)
+    };
+
+    let mut strings = StringTable::new();
+    let mut symbols = vec![0u8; SYMBOL_SIZE];
+
+    let mut ordered: Vec<&Symbol> = info.symbols.iter().collect();
+    ordered.sort_by_key(|symbol| symbol.global);
+
+    let first_global = 1 + ordered.iter().filter(|symbol| !symbol.global).count();
+
+    for symbol in ordered {
+        let kind = match symbol.section {
+            SymbolSection::Text => STT_FUNC,
+            SymbolSection::Data => STT_OBJECT,
+        };
+
+        let bind = if symbol.global { STB_GLOBAL } else { STB_LOCAL };
+
+        endian.put_u32(&mut symbols, strings.add(&symbol.name));
+        endian.put_u32(&mut symbols, symbol.address);
+        endian.put_u32(&mut symbols, symbol.data.as_ref().map(|data| data.size).unwrap_or(0));
+        symbols.push(bind << 4 | kind);
+        symbols.push(0);
+        endian.put_u16(&mut symbols, section_of(symbol.address));
+    }
+
+    let mut lines = vec![];
+
+    for (address, line) in &info.lines {
+        endian.put_u32(&mut lines, *address);
+        endian.put_u32(&mut lines, *line);
+    }
+
+    let symtab_index = headers.len() as u32;
+
+    // (name, type, data, link, info, entry size) of each appended section.
+    let mut appended = vec![
+        (".symtab", SHT_SYMTAB, symbols, symtab_index + 1, first_global as u32, SYMBOL_SIZE as u32),
+        (".strtab", SHT_STRTAB, strings.data, 0, 0, 0),
+        (LINES_SECTION, SHT_PROGBITS, lines, 0, 0, 8),
+    ];
+
+    if let Some(source) = &info.source {
+        appended.push((SOURCE_SECTION, SHT_PROGBITS, source.as_bytes().to_vec(), 0, 0, 0))
+    }
+
+    for (name, kind, data, link, info, entry_size) in appended {
+        align(&mut bytes);
+
+        headers.push(SectionHeader {
+            name: names.add(name),
+            kind,
+            flags: 0,
+            address: 0,
+            offset: bytes.len() as u32,
+            size: data.len() as u32,
+            link,
+            info,
+            align: if kind == SHT_STRTAB || name == SOURCE_SECTION { 1 } else { 4 },
+            entry_size,
+        });
+
+        bytes.extend(data);
+    }
+
+    let names_index = headers.len();
+    let names_name = names.add(".shstrtab");
+
+    align(&mut bytes);
+
+    headers.push(SectionHeader {
+        name: names_name,
+        kind: SHT_STRTAB,
+        flags: 0,
+        address: 0,
+        offset: bytes.len() as u32,
+        size: names.data.len() as u32,
+        link: 0,
+        info: 0,
+        align: 1,
+        entry_size: 0,
+    });
+
+    bytes.extend(names.data);
+
+    align(&mut bytes);
+
+    let section_offset = bytes.len() as u32;
+
+    for header in &headers {
+        header.write(endian, &mut bytes)
+    }
+
+    endian.set_u32(&mut bytes, 0x20, section_offset);
+    endian.set_u16(&mut bytes, 0x2E, SECTION_HEADER_SIZE as u16);
+    endian.set_u16(&mut bytes, 0x30, headers.len() as u16);
+    endian.set_u16(&mut bytes, 0x32, names_index as u16);
+
+    bytes
+}
+
+// Reads back what append_debug_info wrote. Symbols are read from any ELF with a .symtab,
+// like the ones mips-linux-gnu-gcc produces.
+pub fn read_debug_info(bytes: &[u8]) -> Option<ElfDebugInfo> {
+    let endian = endian(bytes)?;
+
+    if bytes.len() < ELF_HEADER_SIZE {
+        return None
+    }
+
+    let section_offset = endian.u32(bytes, 0x20)? as usize;
+    let section_count = endian.u16(bytes, 0x30)? as usize;
+    let names_index = endian.u16(bytes, 0x32)? as usize;
+
+    if section_offset == 0 || section_count == 0 {
+        return None
+    }
+
+    let headers: Vec<SectionHeader> = (0..section_count)
+        .map(|index| SectionHeader::read(endian, bytes, section_offset + index * SECTION_HEADER_SIZE))
+        .collect::<Option<_>>()?;
+
+    let names = headers.get(names_index)?.data(bytes)?;
+    let named = |name: &str| {
+        headers.iter().find(|header| read_string(names, header.name as usize).as_deref() == Some(name))
+    };
+
+    let lines: Vec<(u32, u32)> = named(LINES_SECTION)
+        .and_then(|header| header.data(bytes))
+        .map(|data| {
+            data.chunks_exact(8)
+                .filter_map(|pair| Some((endian.u32(pair, 0)?, endian.u32(pair, 4)?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let source = named(SOURCE_SECTION)
+        .and_then(|header| header.data(bytes))
+        .map(|data| String::from_utf8_lossy(data).to_string());
+
+    let mut info = ElfDebugInfo { symbols: vec![], lines, source };
+    info.lines.sort();
+
+    let Some(symtab) = headers.iter().find(|header| header.kind == SHT_SYMTAB) else {
+        return Some(info)
+    };
+
+    let strings = headers.get(symtab.link as usize).and_then(|header| header.data(bytes))?;
+    let data = symtab.data(bytes)?;
+
+    // Entry zero is the null symbol.
+    for entry in data.chunks_exact(SYMBOL_SIZE).skip(1) {
+        let name = read_string(strings, endian.u32(entry, 0)? as usize)?;
+
+        let kind = entry[12] & 0xF;
+        let index = endian.u16(entry, 14)?;
+
+        // Compilers also list section and file symbols, and symbols from other objects.
+        if name.is_empty() || kind == STT_SECTION || kind == STT_FILE || index == SHN_UNDEF {
+            continue
+        }
+
+        let address = endian.u32(entry, 4)?;
+
+        let section = match kind {
+            STT_FUNC => SymbolSection::Text,
+            STT_OBJECT => SymbolSection::Data,
+            _ => {
+                let executable = headers.get(index as usize).map(|header| header.flags & SHF_EXECINSTR != 0);
+
+                if executable.unwrap_or(false) { SymbolSection::Text } else { SymbolSection::Data }
+            }
+        };
+
+        info.symbols.push(Symbol {
+            name,
+            address,
+            section,
+            global: entry[12] >> 4 != STB_LOCAL,
+            path: None,
+            location: info.line_at(address).map(|line| SourcePosition { line: line as usize, column: 0 }),
+            data: None,
+        })
+    }
+
+    info.symbols.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+
+    Some(info)
+}
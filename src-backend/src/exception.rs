@@ -0,0 +1,384 @@
+use std::collections::VecDeque;
+use titan::cpu::error::Error;
+use titan::cpu::error::Error::{CpuBreak, CpuInvalid, CpuTrap, MemoryAlign, MemoryUnmapped};
+use titan::cpu::Memory;
+use titan::execution::Executor;
+use titan::execution::trackers::Tracker;
+use crate::build::TIME_TRAVEL_HISTORY_SIZE;
+
+// Kernel text is expected to place the general exception vector here (.ktext 0x80000180).
+// The compact memory layouts move it, see Coprocessor0::handler.
+pub const EXCEPTION_HANDLER: u32 = 0x80000180;
+
+pub const BAD_VADDR_REGISTER: u32 = 8;
+pub const COUNT_REGISTER: u32 = 9;
+pub const COMPARE_REGISTER: u32 = 11;
+pub const STATUS_REGISTER: u32 = 12;
+pub const CAUSE_REGISTER: u32 = 13;
+pub const EPC_REGISTER: u32 = 14;
+
+pub const STATUS_IE: u32 = 1 << 0;
+pub const STATUS_EXL: u32 = 1 << 1;
+
+const CAUSE_CODE_MASK: u32 = 0x1F << 2;
+const INTERRUPT_MASK: u32 = 0xFF << 8;
+
+// Cause/Status interrupt bits, keyboard uses the same line as MARS.
+pub const INTERRUPT_KEYBOARD: u32 = 1 << 8;
+pub const INTERRUPT_DISPLAY: u32 = 1 << 9;
+// Digital Lab Sim lines, also matching MARS.
+pub const INTERRUPT_LAB_TIMER: u32 = 1 << 10;
+pub const INTERRUPT_KEYPAD: u32 = 1 << 11;
+pub const INTERRUPT_MOUSE: u32 = 1 << 12;
+// Compare register of the MMIO timer block, separate from Count/Compare.
+pub const INTERRUPT_CLOCK: u32 = 1 << 13;
+pub const INTERRUPT_TIMER: u32 = 1 << 15;
+
+// Same reset value as MARS: all interrupts masked in, user mode, interrupts enabled.
+const STATUS_DEFAULT: u32 = 0x0000FF11;
+
+const COP0_OPCODE: u32 = 0x10;
+const COP0_MFC0: u32 = 0x00;
+const COP0_MTC0: u32 = 0x04;
+const COP0_ERET: u32 = 0x42000018;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExceptionCode {
+    Interrupt = 0,
+    AddressLoad = 4,
+    AddressStore = 5,
+    Syscall = 8,
+    Breakpoint = 9,
+    ReservedInstruction = 10,
+    Overflow = 12,
+    Trap = 13,
+}
+
+// Registers saved before a change, so time travel can put them back.
+#[derive(Copy, Clone)]
+struct Checkpoint {
+    bad_vaddr: u32,
+    count: u32,
+    compare: u32,
+    status: u32,
+    cause: u32,
+    epc: u32,
+}
+
+pub struct Coprocessor0 {
+    pub bad_vaddr: u32,
+    pub count: u32,
+    pub compare: u32,
+    pub status: u32,
+    pub cause: u32,
+    pub epc: u32,
+    // Address of the general exception vector.
+    pub handler: u32,
+    // Instruction count Count was last advanced to.
+    synced: u64,
+    // Keyed by the instruction count of the change.
+    history: VecDeque<(u64, Checkpoint)>,
+}
+
+impl Default for Coprocessor0 {
+    fn default() -> Self {
+        Coprocessor0::new(EXCEPTION_HANDLER)
+    }
+}
+
+impl Coprocessor0 {
+    pub fn new(handler: u32) -> Self {
+        Coprocessor0 {
+            bad_vaddr: 0,
+            count: 0,
+            compare: 0,
+            status: STATUS_DEFAULT,
+            cause: 0,
+            epc: 0,
+            handler,
+            synced: 0,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn read(&self, register: u32) -> Option<u32> {
+        match register {
+            BAD_VADDR_REGISTER => Some(self.bad_vaddr),
+            COUNT_REGISTER => Some(self.count),
+            COMPARE_REGISTER => Some(self.compare),
+            STATUS_REGISTER => Some(self.status),
+            CAUSE_REGISTER => Some(self.cause),
+            EPC_REGISTER => Some(self.epc),
+            _ => None,
+        }
+    }
+
+    pub fn write(&mut self, register: u32, value: u32) {
+        match register {
+            BAD_VADDR_REGISTER => self.bad_vaddr = value,
+            COUNT_REGISTER => self.count = value,
+            COMPARE_REGISTER => {
+                // Writing Compare acknowledges the timer interrupt.
+                self.compare = value;
+                self.cause &= !INTERRUPT_TIMER;
+            }
+            STATUS_REGISTER => self.status = value,
+            CAUSE_REGISTER => self.cause = value,
+            EPC_REGISTER => self.epc = value,
+            _ => {}
+        }
+    }
+
+    // Saves the registers before an exception, interrupt, mtc0 or eret changes them.
+    pub fn checkpoint(&mut self) {
+        if self.history.len() >= TIME_TRAVEL_HISTORY_SIZE {
+            self.history.pop_front();
+        }
+
+        let checkpoint = Checkpoint {
+            bad_vaddr: self.bad_vaddr,
+            count: self.count,
+            compare: self.compare,
+            status: self.status,
+            cause: self.cause,
+            epc: self.epc,
+        };
+
+        self.history.push_back((self.synced, checkpoint))
+    }
+
+    // Time travel back to instruction count now, undoing later changes and winding Count back.
+    pub fn rewind(&mut self, now: u64) {
+        while let Some((at, checkpoint)) = self.history.back().copied() {
+            if at <= now {
+                break
+            }
+
+            self.history.pop_back();
+
+            self.bad_vaddr = checkpoint.bad_vaddr;
+            self.count = checkpoint.count;
+            self.compare = checkpoint.compare;
+            self.status = checkpoint.status;
+            self.cause = checkpoint.cause;
+            self.epc = checkpoint.epc;
+            self.synced = at;
+        }
+
+        if self.synced > now {
+            self.count = self.count.wrapping_sub((self.synced - now) as u32);
+            self.synced = now;
+        }
+    }
+
+    pub fn in_exception(&self) -> bool {
+        self.status & STATUS_EXL != 0
+    }
+
+    pub fn set_interrupt(&mut self, line: u32, pending: bool) {
+        if pending {
+            self.cause |= line
+        } else {
+            self.cause &= !line
+        }
+    }
+
+    // Advances Count to the instruction count now.
+    pub fn sync(&mut self, now: u64) {
+        if now > self.synced {
+            self.tick((now - self.synced).min(u32::MAX as u64) as u32)
+        }
+
+        self.synced = now
+    }
+
+    // Instructions until Count reaches Compare.
+    pub fn next_event(&self) -> Option<u32> {
+        Some(self.compare.wrapping_sub(self.count)).filter(|distance| *distance != 0)
+    }
+
+    // Advances Count, raising the timer interrupt when it passes Compare.
+    pub fn tick(&mut self, cycles: u32) {
+        let before = self.count;
+
+        self.count = self.count.wrapping_add(cycles);
+
+        let distance = self.compare.wrapping_sub(before);
+
+        if distance != 0 && distance <= cycles {
+            self.cause |= INTERRUPT_TIMER
+        }
+    }
+
+    pub fn interrupt_ready(&self) -> bool {
+        self.status & STATUS_IE != 0
+            && !self.in_exception()
+            && self.cause & self.status & INTERRUPT_MASK != 0
+    }
+
+    pub fn enter(&mut self, code: ExceptionCode, epc: u32, bad_vaddr: Option<u32>) {
+        self.cause = (self.cause & !CAUSE_CODE_MASK) | ((code as u32) << 2);
+        self.epc = epc;
+        self.status |= STATUS_EXL;
+
+        if let Some(address) = bad_vaddr {
+            self.bad_vaddr = address;
+        }
+    }
+}
+
+fn is_store(instruction: u32) -> bool {
+    // sb, sh, swl, sw, swr
+    matches!(instruction >> 26, 0x28..=0x2E)
+}
+
+fn is_overflow(instruction: u32) -> bool {
+    let opcode = instruction >> 26;
+    let funct = instruction & 0x3F;
+
+    // addi or add/sub
+    opcode == 0x08 || (opcode == 0 && matches!(funct, 0x20 | 0x22))
+}
+
+fn has_handler<Mem: Memory, Track: Tracker<Mem>>(debugger: &Executor<Mem, Track>, handler: u32) -> bool {
+    debugger.with_memory(|memory| memory.get_u32(handler).is_ok())
+}
+
+// Runs mfc0, mtc0 and eret, which the cpu reports as invalid instructions.
+fn execute_cop0<Mem: Memory, Track: Tracker<Mem>>(
+    debugger: &Executor<Mem, Track>,
+    cop0: &mut Coprocessor0,
+    instruction: u32,
+) -> bool {
+    if instruction >> 26 != COP0_OPCODE {
+        return false
+    }
+
+    if instruction == COP0_ERET {
+        cop0.checkpoint();
+
+        let epc = cop0.epc;
+
+        cop0.status &= !STATUS_EXL;
+
+        debugger.with_state(|state| state.registers.pc = epc);
+
+        return true
+    }
+
+    let rt = ((instruction >> 16) & 0x1F) as usize;
+    let rd = (instruction >> 11) & 0x1F;
+
+    match (instruction >> 21) & 0x1F {
+        COP0_MFC0 => {
+            let value = cop0.read(rd).unwrap_or(0);
+
+            debugger.with_state(|state| {
+                if rt != 0 {
+                    state.registers.line[rt] = value
+                }
+            });
+
+            true
+        }
+        COP0_MTC0 => {
+            let value = debugger.with_state(|state| state.registers.line[rt]);
+
+            cop0.checkpoint();
+
+            cop0.write(rd, value);
+
+            true
+        }
+        _ => false
+    }
+}
+
+// Jumps to the kernel handler with Cause/EPC/BadVAddr filled in.
+// Returns false (leaving the error to be reported) if no handler is loaded.
+pub fn raise_exception<Mem: Memory, Track: Tracker<Mem>>(
+    debugger: &Executor<Mem, Track>,
+    cop0: &mut Coprocessor0,
+    code: ExceptionCode,
+    epc: u32,
+    bad_vaddr: Option<u32>,
+) -> bool {
+    // Faulting inside the handler would loop forever.
+    if cop0.in_exception() || !has_handler(debugger, cop0.handler) {
+        return false
+    }
+
+    cop0.checkpoint();
+    cop0.enter(code, epc, bad_vaddr);
+
+    let handler = cop0.handler;
+
+    debugger.with_state(|state| state.registers.pc = handler);
+
+    true
+}
+
+// Returns true if the error was handled and execution can continue.
+pub fn handle_exception<Mem: Memory, Track: Tracker<Mem>>(
+    debugger: &Executor<Mem, Track>,
+    cop0: &mut Coprocessor0,
+    error: Error,
+) -> bool {
+    // pc has already moved past the faulting instruction.
+    let pc = debugger.with_state(|state| state.registers.pc.wrapping_sub(4));
+    let instruction = debugger.with_memory(|memory| memory.get_u32(pc).ok());
+
+    let (code, bad_vaddr) = match error {
+        CpuInvalid(value) => {
+            if execute_cop0(debugger, cop0, value) {
+                return true
+            }
+
+            (ExceptionCode::ReservedInstruction, None)
+        }
+        MemoryAlign(address) | MemoryUnmapped(address) => {
+            let code = if instruction.map(is_store).unwrap_or(false) {
+                ExceptionCode::AddressStore
+            } else {
+                ExceptionCode::AddressLoad
+            };
+
+            (code, Some(address))
+        }
+        CpuTrap => {
+            let code = if instruction.map(is_overflow).unwrap_or(false) {
+                ExceptionCode::Overflow
+            } else {
+                ExceptionCode::Trap
+            };
+
+            (code, None)
+        }
+        CpuBreak => (ExceptionCode::Breakpoint, None),
+        _ => return false
+    };
+
+    raise_exception(debugger, cop0, code, pc, bad_vaddr)
+}
+
+// A syscall that faulted on memory (store is set if it was writing) or asked for an unknown service.
+// Reported against the syscall instruction, like MARS. error is None for unknown services.
+pub fn handle_syscall_exception<Mem: Memory, Track: Tracker<Mem>>(
+    debugger: &Executor<Mem, Track>,
+    cop0: &mut Coprocessor0,
+    error: Option<Error>,
+    store: bool,
+) -> bool {
+    let pc = debugger.with_state(|state| state.registers.pc.wrapping_sub(4));
+
+    let address_code = if store { ExceptionCode::AddressStore } else { ExceptionCode::AddressLoad };
+
+    let (code, bad_vaddr) = match error {
+        None => (ExceptionCode::Syscall, None),
+        Some(MemoryAlign(address) | MemoryUnmapped(address)) => (address_code, Some(address)),
+        // Strings or buffers running past the end of the address space.
+        Some(_) => (address_code, None),
+    };
+
+    raise_exception(debugger, cop0, code, pc, bad_vaddr)
+}
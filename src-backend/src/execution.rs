@@ -1,7 +1,7 @@
-use crate::display::{FlushDisplayBody, read_display};
+use crate::display::{DisplayFormat, FlushDisplayBody, FlushDisplayState, read_display};
 use crate::syscall::{SyscallDelegate, SyscallResult};
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use async_trait::async_trait;
 use titan::cpu::error::Error::{CpuTrap, MemoryAlign, MemoryUnmapped};
 use titan::cpu::{Memory, State};
@@ -14,6 +14,7 @@ use titan::execution::trackers::Tracker;
 use titan::unit::instruction::InstructionDecoder;
 use titan::unit::suggestions::MemoryErrorReason;
 use crate::device::ExecutionState;
+use crate::logpoint::LogpointTemplate;
 
 #[derive(Serialize)]
 #[serde(tag = "type")]
@@ -181,15 +182,25 @@ pub trait ExecutionDevice: Send + Sync {
     fn pause(&self);
 
     fn set_breakpoints(&self, breakpoints: HashSet<u32>);
+    // pc -> message template, replaces any previous logpoints.
+    fn set_logpoints(&self, logpoints: HashMap<u32, String>) -> Result<(), String>;
 
     fn read_bytes(&self, address: u32, count: u32) -> Option<Vec<Option<u8>>>;
-    fn read_display(&self, address: u32, width: u32, height: u32) -> Option<Vec<u8>>;
+    fn read_display(&self, address: u32, width: u32, height: u32, format: DisplayFormat) -> Option<Vec<u8>>;
+    // Picks up the display rows written since the last flush.
+    fn flush_display(&self, display: &mut FlushDisplayState);
 
     fn write_bytes(&self, address: u32, bytes: Vec<u8>);
     fn write_register(&self, register: u32, value: u32);
 
     fn wake_sync(&self);
-    fn post_key(&self, key: char, up: bool);
+    fn post_key(&self, key: &str, up: bool);
+    // Cursor in display units, buttons is a mask of MOUSE_LEFT, MOUSE_RIGHT and MOUSE_MIDDLE.
+    fn post_mouse(&self, x: u32, y: u32, buttons: u8);
+    // Passes front end input to the device with this name, the shape is up to the device.
+    fn device_input(&self, name: &str, input: serde_json::Value) -> Result<(), String>;
+    // Device name -> state, for devices that publish one.
+    fn device_snapshots(&self) -> HashMap<String, serde_json::Value>;
     fn post_input(&self, text: String);
 }
 
@@ -206,7 +217,9 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice for Executi
         if let Some(breakpoints) = options.breakpoints {
             let breakpoints_set = HashSet::from_iter(breakpoints.iter().copied());
 
-            debugger.set_breakpoints(breakpoints_set);
+            let merged = state.lock().unwrap().logpoints.set_breakpoints(breakpoints_set);
+
+            debugger.set_breakpoints(merged);
         }
         
         let is_breakpoint = debugger.is_breakpoint();
@@ -222,7 +235,9 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice for Executi
             state.lock().unwrap().clear_cancelled();
         }
 
-        let delegate = SyscallDelegate::new(state);
+        let delegate = SyscallDelegate::new(
+            state, self.cop0.clone(), self.devices.clone(), options.display.clone()
+        );
 
         let (frame, result) = {
             if let Some(batch) = &options.batch {
@@ -269,7 +284,26 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice for Executi
     }
 
     fn set_breakpoints(&self, breakpoints: HashSet<u32>) {
-        self.debugger.set_breakpoints(breakpoints)
+        let merged = self.delegate.lock().unwrap().logpoints.set_breakpoints(breakpoints);
+
+        self.debugger.set_breakpoints(merged)
+    }
+
+    fn set_logpoints(&self, logpoints: HashMap<u32, String>) -> Result<(), String> {
+        let mut points = HashMap::new();
+
+        for (pc, message) in logpoints {
+            let template = LogpointTemplate::parse(&message)
+                .map_err(|error| format!("Logpoint at 0x{:08x}: {}", pc, error))?;
+
+            points.insert(pc, template);
+        }
+
+        let merged = self.delegate.lock().unwrap().logpoints.set_points(points);
+
+        self.debugger.set_breakpoints(merged);
+
+        Ok(())
     }
 
     fn read_bytes(&self, address: u32, count: u32) -> Option<Vec<Option<u8>>> {
@@ -285,12 +319,18 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice for Executi
         Some(value)
     }
 
-    fn read_display(&self, address: u32, width: u32, height: u32) -> Option<Vec<u8>> {
+    fn read_display(&self, address: u32, width: u32, height: u32, format: DisplayFormat) -> Option<Vec<u8>> {
+        let hidden = self.devices.registry.ranges_at(self.mmio);
+
         self.debugger.with_memory(|memory| {
-            read_display(address, width, height, memory)
+            read_display(address, width, height, format, &hidden, memory)
         })
     }
 
+    fn flush_display(&self, display: &mut FlushDisplayState) {
+        self.debugger.with_memory(|memory| display.flush(memory))
+    }
+
     fn write_bytes(&self, address: u32, bytes: Vec<u8>) {
         self.debugger.with_memory(|memory| {
             for (index, byte) in bytes.iter().enumerate() {
@@ -317,8 +357,24 @@ impl<Mem: Memory + Send, Track: Tracker<Mem> + Send> ExecutionDevice for Executi
         }
     }
 
-    fn post_key(&self, key: char, up: bool) {
-        self.keyboard.lock().unwrap().push_key(key, up)
+    fn post_key(&self, key: &str, up: bool) {
+        let input = serde_json::json!({ "key": key, "up": up });
+
+        self.devices.registry.input("keyboard", input).ok();
+    }
+
+    fn post_mouse(&self, x: u32, y: u32, buttons: u8) {
+        let input = serde_json::json!({ "x": x, "y": y, "buttons": buttons });
+
+        self.devices.registry.input("mouse", input).ok();
+    }
+
+    fn device_input(&self, name: &str, input: serde_json::Value) -> Result<(), String> {
+        self.devices.registry.input(name, input)
+    }
+
+    fn device_snapshots(&self) -> HashMap<String, serde_json::Value> {
+        self.devices.registry.snapshots()
     }
 
     fn post_input(&self, text: String) {
@@ -354,21 +410,20 @@ impl<Mem: Memory> ExecutionRewindable for ExecutionState<WatchedMemory<Mem>, His
     fn rewind(&self, count: u32) -> ResumeResult {
         for _ in 0 .. count {
             let entry = self.debugger.with_tracker(|tracker| tracker.pop());
-            let Some(entry) = entry else {
-                let frame = self.debugger.frame();
-
-                return self.debugger.with_state(|state| {
-                    ResumeResult::from_frame(frame, &[], None, state)
-                })
-            };
+            let Some(entry) = entry else { break };
 
             self.debugger.pause();
 
             self.debugger.with_state(|state| {
                 entry.apply(&mut state.registers, &mut state.memory.backing);
             });
+
+            self.devices.registry.rewind(1);
         }
 
+        // Exceptions, interrupts and cop0 instructions change cop0 outside the tracker.
+        self.cop0.lock().unwrap().rewind(self.devices.registry.now());
+
         let frame = self.debugger.frame();
 
         self.debugger.with_state(|state| {
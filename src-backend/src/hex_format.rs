@@ -2,6 +2,9 @@ use std::cmp::min;
 use byteorder::ByteOrder;
 use serde::{Deserialize, Serialize};
 
+// Caps decode_hex, so a count*value run can't ask for more than a 32 bit memory could use.
+pub const MAX_HEX_VALUES: usize = 16 * 1024 * 1024;
+
 #[derive(Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all="snake_case")]
 pub enum HexEncoding {
@@ -66,3 +69,62 @@ pub fn encode_hex(data: &[u64]) -> String {
 
     format!("{leading}\n{body}")
 }
+
+// Reads "v3.0 hex words plain" text back, including Logisim's count*value runs.
+pub fn decode_hex(text: &str) -> Result<Vec<u64>, String> {
+    let mut lines = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty());
+
+    match lines.next() {
+        Some("v3.0 hex words plain" | "v2.0 raw") => { }
+        Some(header) if header.starts_with("v3.0 hex") => {
+            return Err(format!("Only plain hex files are supported, found \"{}\"", header))
+        }
+        _ => return Err("Missing \"v3.0 hex words plain\" header".to_string())
+    }
+
+    let mut result = vec![];
+
+    for token in lines.flat_map(|line| line.split_whitespace()) {
+        let (count, value) = match token.split_once('*') {
+            Some((count, value)) => (
+                count.parse::<usize>().map_err(|_| format!("Invalid run length in \"{}\"", token))?,
+                value
+            ),
+            None => (1, token)
+        };
+
+        let value = u64::from_str_radix(value, 16)
+            .map_err(|_| format!("Invalid hex value \"{}\"", token))?;
+
+        if count > MAX_HEX_VALUES - result.len() {
+            return Err(format!("Hex file has more than {} values", MAX_HEX_VALUES))
+        }
+
+        result.extend(std::iter::repeat(value).take(count));
+    }
+
+    Ok(result)
+}
+
+// Inverse of encode_hex_with_encoding, giving the bytes in memory order.
+pub fn decode_hex_with_encoding(text: &str, encoding: HexEncoding) -> Result<Vec<u8>, String> {
+    let values = decode_hex(text)?;
+
+    let limit = match encoding {
+        HexEncoding::Byte => 0xFF,
+        HexEncoding::Little32 | HexEncoding::Big32 => 0xFFFFFFFF,
+    };
+
+    if let Some(value) = values.iter().find(|value| **value > limit) {
+        return Err(format!("Hex value {:x} doesn't fit the chosen encoding", value))
+    }
+
+    Ok(match encoding {
+        HexEncoding::Byte => values.into_iter().map(|value| value as u8).collect(),
+        HexEncoding::Little32 => values.into_iter().flat_map(|value| (value as u32).to_le_bytes()).collect(),
+        HexEncoding::Big32 => values.into_iter().flat_map(|value| (value as u32).to_be_bytes()).collect(),
+    })
+}
@@ -1,16 +1,120 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use titan::cpu::error;
 use titan::cpu::error::Error::MemoryUnmapped;
-use titan::cpu::memory::section::ListenResponder;
+use crate::exception::{INTERRUPT_DISPLAY, INTERRUPT_KEYBOARD};
+use crate::mmio::{parse_input, DeviceOutput, MemoryView, MmioDevice};
 
+// Laid out like the MARS Keyboard and Display MMIO Simulator.
+//   0xFFFF0000 receiver control (bit 0 ready, bit 1 interrupt enable)
+//   0xFFFF0004 receiver data (reading takes the next key)
+//   0xFFFF0008 transmitter control (bit 0 ready, bit 1 interrupt enable)
+//   0xFFFF000C transmitter data (writing prints a character to the console)
 pub const KEYBOARD_ADDRESS: u32 = 0xFFFF0000;
+pub const KEYBOARD_DATA: u32 = 0xFFFF0004;
+pub const TRANSMITTER_CONTROL: u32 = 0xFFFF0008;
+pub const TRANSMITTER_DATA: u32 = 0xFFFF000C;
+// 0xFFFF0070 byte 0 is the number of queued keys, byte 1 is 1 if keys were dropped (write to clear).
+// 0xFFFF0074 is the modifier mask (KEYBOARD_SHIFT, KEYBOARD_CONTROL, ...).
+// 0xFFFF0080 holds one byte per key code, 1 while that key is held down.
+pub const KEYBOARD_STATUS: u32 = 0xFFFF0070;
+pub const KEYBOARD_MODIFIERS: u32 = 0xFFFF0074;
 pub const KEYBOARD_HOLDING: u32 = 0xFFFF0080;
 pub const KEYBOARD_SELECTOR: u32 = KEYBOARD_ADDRESS >> 16;
 
+// Keys past this are dropped and the overflow flag is set.
+pub const KEYBOARD_QUEUE_SIZE: usize = 64;
+
+pub const KEYBOARD_SHIFT: u8 = 0b0001;
+pub const KEYBOARD_CONTROL: u8 = 0b0010;
+pub const KEYBOARD_ALT: u8 = 0b0100;
+pub const KEYBOARD_META: u8 = 0b1000;
+
+// Key codes below 0x80 are ASCII. Keys without a character use these scancodes,
+// both in the receiver data register and as an index into KEYBOARD_HOLDING.
+// Modifier keys update the holding array and modifier mask but are never queued.
+pub const SCANCODES: [(&str, u8); 30] = [
+    ("ArrowUp", 0x80),
+    ("ArrowDown", 0x81),
+    ("ArrowLeft", 0x82),
+    ("ArrowRight", 0x83),
+    ("Shift", 0x84),
+    ("Control", 0x85),
+    ("Alt", 0x86),
+    ("Meta", 0x87),
+    ("Home", 0x88),
+    ("End", 0x89),
+    ("PageUp", 0x8A),
+    ("PageDown", 0x8B),
+    ("Insert", 0x8C),
+    ("CapsLock", 0x8D),
+    ("F1", 0x90),
+    ("F2", 0x91),
+    ("F3", 0x92),
+    ("F4", 0x93),
+    ("F5", 0x94),
+    ("F6", 0x95),
+    ("F7", 0x96),
+    ("F8", 0x97),
+    ("F9", 0x98),
+    ("F10", 0x99),
+    ("F11", 0x9A),
+    ("F12", 0x9B),
+    // Named keys that do have an ASCII code.
+    ("Enter", b'\n'),
+    ("Tab", b'\t'),
+    ("Backspace", 0x08),
+    ("Escape", 0x1B),
+];
+
+fn key_code(key: &str) -> Option<u8> {
+    let mut chars = key.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return if c.is_ascii() { Some(c as u8) } else { None };
+    }
+
+    SCANCODES
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, code)| *code)
+}
+
+fn modifier(code: u8) -> Option<u8> {
+    match code {
+        0x84 => Some(KEYBOARD_SHIFT),
+        0x85 => Some(KEYBOARD_CONTROL),
+        0x86 => Some(KEYBOARD_ALT),
+        0x87 => Some(KEYBOARD_META),
+        _ => None,
+    }
+}
+
+pub const MMIO_READY: u8 = 0b01;
+pub const MMIO_INTERRUPT_ENABLE: u8 = 0b10;
+
+// Instructions the transmitter stays busy after a write, MARS defaults to 5.
+pub const DEFAULT_TRANSMIT_DELAY: u32 = 5;
+
 pub struct KeyboardState {
-    last: Option<char>,
-    keys: Vec<char>,
-    holding: [bool; 128],
+    keys: VecDeque<u8>,
+    overflow: bool,
+    modifiers: u8,
+    holding: [bool; 256],
+    interrupt_enabled: bool,
+    transmit_delay: u32,
+    transmit_busy: u32,
+    transmit_interrupt_enabled: bool,
+    output: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct KeyInput {
+    key: String,
+    up: bool,
 }
 
 pub struct KeyboardHandler {
@@ -18,31 +122,87 @@ pub struct KeyboardHandler {
 }
 
 impl KeyboardState {
-    pub fn push_key(&mut self, key: char, up: bool) {
+    // Takes a single character or one of the key names in SCANCODES, other keys are ignored.
+    pub fn push_key(&mut self, key: &str, up: bool) {
+        let Some(code) = key_code(key) else { return };
+
+        self.holding[code as usize] = !up;
+
+        if let Some(mask) = modifier(code) {
+            if up {
+                self.modifiers &= !mask
+            } else {
+                self.modifiers |= mask
+            }
+
+            return
+        }
+
         if !up {
-            self.keys.push(key)
+            if self.keys.len() < KEYBOARD_QUEUE_SIZE {
+                self.keys.push_back(code)
+            } else {
+                self.overflow = true
+            }
         }
+    }
 
-        let offset = key as usize;
+    // Level triggered: stays pending until the program reads every key.
+    pub fn interrupt_pending(&self) -> bool {
+        self.interrupt_enabled && !self.keys.is_empty()
+    }
 
-        if offset < self.holding.len() {
-            self.holding[offset] = !up
-        }
+    // Level triggered like MARS: pending for as long as the transmitter is ready and the interrupt is enabled.
+    pub fn transmit_interrupt_pending(&self) -> bool {
+        self.transmit_interrupt_enabled && self.transmit_busy == 0
     }
 
-    fn pop_key(&mut self) -> Option<char> {
-        if let Some(value) = self.keys.pop() {
-            self.last = Some(value)
-        }
+    pub fn set_transmit_delay(&mut self, delay: u32) {
+        self.transmit_delay = delay
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        self.transmit_busy = self.transmit_busy.saturating_sub(cycles);
+    }
+
+    // Instructions until the transmitter is ready again.
+    pub fn next_event(&self) -> Option<u32> {
+        Some(self.transmit_busy).filter(|busy| *busy > 0)
+    }
+
+    // Characters written to the transmitter since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
 
-        self.last
+    // Characters written while the transmitter is busy are still printed, they just restart the delay.
+    fn transmit(&mut self, value: u8) {
+        self.output.push(value);
+        self.transmit_busy = self.transmit_delay;
+    }
+
+    fn control(ready: bool, interrupt_enabled: bool) -> u8 {
+        let ready = if ready { MMIO_READY } else { 0 };
+        let interrupt = if interrupt_enabled { MMIO_INTERRUPT_ENABLE } else { 0 };
+
+        ready | interrupt
+    }
+
+    fn pop_key(&mut self) -> Option<u8> {
+        self.keys.pop_front()
     }
 
     pub fn new() -> KeyboardState {
         KeyboardState {
-            last: None,
-            keys: vec![],
-            holding: [false; 128],
+            keys: VecDeque::new(),
+            overflow: false,
+            modifiers: 0,
+            holding: [false; 256],
+            interrupt_enabled: false,
+            transmit_delay: DEFAULT_TRANSMIT_DELAY,
+            transmit_busy: 0,
+            transmit_interrupt_enabled: false,
+            output: vec![],
         }
     }
 }
@@ -55,10 +215,23 @@ impl KeyboardHandler {
     }
 }
 
-impl ListenResponder for KeyboardHandler {
+impl MmioDevice for KeyboardHandler {
+    fn name(&self) -> &'static str {
+        "keyboard"
+    }
+
+    fn ranges(&self) -> &[Range<u32>] {
+        &[
+            KEYBOARD_ADDRESS..TRANSMITTER_DATA + 4,
+            KEYBOARD_STATUS..KEYBOARD_MODIFIERS + 4,
+            KEYBOARD_HOLDING..KEYBOARD_HOLDING + 256,
+        ]
+    }
+
     fn read(&self, address: u32) -> error::Result<u8> {
-        let keyboard_handled = KEYBOARD_ADDRESS..KEYBOARD_ADDRESS + 8;
-        let keyboard_holding = KEYBOARD_HOLDING..KEYBOARD_HOLDING + 128;
+        let keyboard_handled = KEYBOARD_ADDRESS..TRANSMITTER_DATA + 4;
+        let keyboard_status = KEYBOARD_STATUS..KEYBOARD_MODIFIERS + 4;
+        let keyboard_holding = KEYBOARD_HOLDING..KEYBOARD_HOLDING + 256;
 
         if keyboard_handled.contains(&address) {
             let offset = address - KEYBOARD_ADDRESS;
@@ -66,14 +239,18 @@ impl ListenResponder for KeyboardHandler {
             let mut state = self.state.lock().unwrap();
 
             Ok(match offset {
-                0 => {
-                    if state.keys.is_empty() {
-                        0
-                    } else {
-                        1
-                    }
-                }
-                4 => state.pop_key().map(|c| c as u8).unwrap_or(0),
+                0 => KeyboardState::control(!state.keys.is_empty(), state.interrupt_enabled),
+                4 => state.pop_key().unwrap_or(0),
+                8 => KeyboardState::control(state.transmit_busy == 0, state.transmit_interrupt_enabled),
+                _ => 0,
+            })
+        } else if keyboard_status.contains(&address) {
+            let state = self.state.lock().unwrap();
+
+            Ok(match address - KEYBOARD_STATUS {
+                0 => state.keys.len() as u8,
+                1 => state.overflow as u8,
+                4 => state.modifiers,
                 _ => 0,
             })
         } else if keyboard_holding.contains(&address) {
@@ -91,7 +268,54 @@ impl ListenResponder for KeyboardHandler {
         }
     }
 
-    fn write(&mut self, address: u32, _: u8) -> error::Result<()> {
-        Err(MemoryUnmapped(address))
+    fn write(&self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        match address.wrapping_sub(KEYBOARD_ADDRESS) {
+            0 => state.interrupt_enabled = value & MMIO_INTERRUPT_ENABLE != 0,
+            8 => state.transmit_interrupt_enabled = value & MMIO_INTERRUPT_ENABLE != 0,
+            12 => state.transmit(value),
+            0x71 => state.overflow = false,
+            // Ready bits, receiver data and the upper bytes of each word are read only.
+            1..=7 | 9..=11 | 13..=15 | 0x70 | 0x72..=0x77 => {}
+            _ => return Err(MemoryUnmapped(address))
+        }
+
+        Ok(())
+    }
+
+    fn tick(&self, cycles: u32) {
+        self.state.lock().unwrap().tick(cycles)
+    }
+
+    fn next_event(&self) -> Option<u32> {
+        self.state.lock().unwrap().next_event()
+    }
+
+    fn lines(&self) -> u32 {
+        INTERRUPT_KEYBOARD | INTERRUPT_DISPLAY
+    }
+
+    fn interrupts(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+
+        let keyboard = if state.interrupt_pending() { INTERRUPT_KEYBOARD } else { 0 };
+        let display = if state.transmit_interrupt_pending() { INTERRUPT_DISPLAY } else { 0 };
+
+        keyboard | display
+    }
+
+    fn update(&self, _memory: &dyn MemoryView, _now: Option<Duration>, output: &mut DeviceOutput) {
+        let text = self.state.lock().unwrap().take_output();
+
+        output.console.extend(text.into_iter().map(|b| b as char))
+    }
+
+    fn input(&self, input: serde_json::Value) -> Result<(), String> {
+        let input: KeyInput = parse_input(input)?;
+
+        self.state.lock().unwrap().push_key(&input.key, input.up);
+
+        Ok(())
     }
 }
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+// The memory configurations offered by MARS (Settings > Memory Configuration).
+#[derive(Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryPreset {
+    #[default]
+    Default,
+    CompactDataAtZero,
+    CompactTextAtZero,
+}
+
+// Where each part of a program lives.
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryLayout {
+    pub text: u32,
+    // Start of the data segment, everything from here to user_high is writable.
+    pub data_segment: u32,
+    pub extern_base: u32,
+    pub global_pointer: u32,
+    pub data: u32,
+    // First address handed out by sbrk (syscall 9).
+    pub heap: u32,
+    pub stack_pointer: u32,
+    // Highest word of the stack.
+    pub stack_base: u32,
+    pub user_high: u32,
+    pub kernel_text: u32,
+    pub exception_handler: u32,
+    pub kernel_data: u32,
+    // Start of the memory mapped devices. Devices are written for 0xFFFF0000 (KEYBOARD_ADDRESS),
+    // the compact layouts move them to 0x7F00 like MARS.
+    pub mmio: u32,
+}
+
+pub type MemoryLayoutBody = Arc<Mutex<MemoryLayout>>;
+
+impl Default for MemoryLayout {
+    fn default() -> MemoryLayout {
+        MemoryLayout::preset(MemoryPreset::Default)
+    }
+}
+
+impl From<MemoryPreset> for MemoryLayout {
+    fn from(preset: MemoryPreset) -> MemoryLayout {
+        MemoryLayout::preset(preset)
+    }
+}
+
+impl MemoryLayout {
+    // Same addresses as MARS 4.5.
+    pub fn preset(preset: MemoryPreset) -> MemoryLayout {
+        match preset {
+            MemoryPreset::Default => MemoryLayout {
+                text: 0x00400000,
+                data_segment: 0x10000000,
+                extern_base: 0x10000000,
+                global_pointer: 0x10008000,
+                data: 0x10010000,
+                heap: 0x10040000,
+                stack_pointer: 0x7FFFEFFC,
+                stack_base: 0x7FFFFFFC,
+                user_high: 0x7FFFFFFF,
+                kernel_text: 0x80000000,
+                exception_handler: 0x80000180,
+                kernel_data: 0x90000000,
+                mmio: 0xFFFF0000,
+            },
+            MemoryPreset::CompactDataAtZero => MemoryLayout {
+                text: 0x00003000,
+                data_segment: 0x00000000,
+                extern_base: 0x00001000,
+                global_pointer: 0x00001800,
+                data: 0x00000000,
+                heap: 0x00002000,
+                stack_pointer: 0x00003FFC,
+                stack_base: 0x00003FFC,
+                user_high: 0x00003FFF,
+                kernel_text: 0x00004000,
+                exception_handler: 0x00004180,
+                kernel_data: 0x00005000,
+                mmio: 0x00007F00,
+            },
+            MemoryPreset::CompactTextAtZero => MemoryLayout {
+                text: 0x00000000,
+                data_segment: 0x00001000,
+                extern_base: 0x00001000,
+                global_pointer: 0x00001800,
+                data: 0x00002000,
+                heap: 0x00003000,
+                stack_pointer: 0x00003FFC,
+                stack_base: 0x00003FFC,
+                user_high: 0x00003FFF,
+                kernel_text: 0x00004000,
+                exception_handler: 0x00004180,
+                kernel_data: 0x00005000,
+                mmio: 0x00007F00,
+            },
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == MemoryLayout::default()
+    }
+
+    // 64KB memory sections covering the data segment through the top of the stack.
+    pub fn writable_selectors(&self) -> Range<usize> {
+        (self.data_segment >> 16) as usize .. (self.user_high >> 16) as usize + 1
+    }
+
+    // 64KB section holding the start of the device page.
+    pub fn mmio_selector(&self) -> u32 {
+        self.mmio >> 16
+    }
+
+    // First address past the stack.
+    pub fn stack_end(&self) -> u32 {
+        self.stack_base.wrapping_add(4)
+    }
+
+    // Placed ahead of the program so the assembler starts each segment at this layout's addresses.
+    pub fn section_header(&self) -> String {
+        format!(
+            ".kdata 0x{:08x}\n.ktext 0x{:08x}\n.data 0x{:08x}\n.text 0x{:08x}\n",
+            self.kernel_data, self.kernel_text, self.data, self.text
+        )
+    }
+}
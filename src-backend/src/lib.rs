@@ -9,3 +9,18 @@ pub mod build;
 pub mod hex_format;
 pub mod regions;
 pub mod midi;
+pub mod logpoint;
+pub mod exception;
+pub mod digital_lab;
+pub mod mmio;
+pub mod mouse;
+pub mod audio;
+pub mod timer;
+pub mod diagnostics;
+pub mod symbols;
+pub mod project;
+pub mod manifest;
+pub mod layout;
+pub mod elf_debug;
+pub mod raw_image;
+pub mod memory_format;
@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+use std::iter::Peekable;
+use std::str::Chars;
+use titan::cpu::{Memory, State};
+
+// Register names by number, without the $.
+pub(crate) const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Register {
+    Line(usize),
+    Pc,
+    Hi,
+    Lo,
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    Constant(u32),
+    Register(Register),
+    Memory { width: u32, address: Box<Expression> },
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ValueFormat {
+    Signed,
+    Unsigned,
+    Hex,
+    Binary,
+    Character,
+}
+
+#[derive(Debug, Clone)]
+enum TemplatePart {
+    Text(String),
+    Value { expression: Expression, format: ValueFormat },
+}
+
+// Message printed (instead of pausing) when execution reaches a logpoint.
+// Values are written as {expression} or {expression:format}, for example
+//   i={$t0} arr[i]={mem32[$a0+$t0*4]:x}
+// Formats are d (signed, default), u (unsigned), x (hex), b (binary) and c (character).
+#[derive(Debug, Clone)]
+pub struct LogpointTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+fn parse_register(name: &str) -> Option<Register> {
+    match name {
+        "pc" => return Some(Register::Pc),
+        "hi" => return Some(Register::Hi),
+        "lo" => return Some(Register::Lo),
+        "s8" => return Some(Register::Line(30)),
+        _ => {}
+    }
+
+    if let Ok(index) = name.parse::<usize>() {
+        return if index < 32 { Some(Register::Line(index)) } else { None };
+    }
+
+    REGISTER_NAMES
+        .iter()
+        .position(|register| *register == name)
+        .map(Register::Line)
+}
+
+struct ExpressionParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> ExpressionParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.chars.next();
+        }
+    }
+
+    fn take_word(&mut self) -> String {
+        let mut word = String::new();
+
+        while let Some(c) = self.chars.peek() {
+            if !c.is_ascii_alphanumeric() && *c != '_' {
+                break;
+            }
+
+            word.push(*c);
+            self.chars.next();
+        }
+
+        word
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("Expected '{}' but found end of expression", expected)),
+        }
+    }
+
+    fn parse_number(word: &str) -> Result<u32, String> {
+        let result = if let Some(hex) = word.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16)
+        } else if let Some(binary) = word.strip_prefix("0b") {
+            u32::from_str_radix(binary, 2)
+        } else {
+            word.parse::<u32>()
+        };
+
+        result.map_err(|_| format!("Invalid number {}", word))
+    }
+
+    fn parse_atom(&mut self) -> Result<Expression, String> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('$') => {
+                self.chars.next();
+
+                let name = self.take_word();
+
+                parse_register(&name)
+                    .map(Expression::Register)
+                    .ok_or_else(|| format!("Unknown register ${}", name))
+            }
+            Some('(') => {
+                self.chars.next();
+
+                let inner = self.parse_sum()?;
+                self.expect(')')?;
+
+                Ok(inner)
+            }
+            Some('-') => {
+                self.chars.next();
+
+                let inner = self.parse_atom()?;
+
+                Ok(Expression::Subtract(Box::new(Expression::Constant(0)), Box::new(inner)))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let word = self.take_word();
+
+                Self::parse_number(&word).map(Expression::Constant)
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let word = self.take_word();
+
+                let width = match word.as_str() {
+                    "mem8" => 1,
+                    "mem16" => 2,
+                    "mem32" | "mem" => 4,
+                    _ => return Err(format!("Unknown value {}, use mem8, mem16 or mem32", word)),
+                };
+
+                self.expect('[')?;
+                let address = self.parse_sum()?;
+                self.expect(']')?;
+
+                Ok(Expression::Memory { width, address: Box::new(address) })
+            }
+            Some(c) => Err(format!("Unexpected character '{}'", c)),
+            None => Err("Expected a value but found end of expression".into()),
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expression, String> {
+        let mut value = self.parse_atom()?;
+
+        loop {
+            self.skip_whitespace();
+
+            if self.chars.peek() != Some(&'*') {
+                return Ok(value);
+            }
+
+            self.chars.next();
+
+            value = Expression::Multiply(Box::new(value), Box::new(self.parse_atom()?));
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expression, String> {
+        let mut value = self.parse_product()?;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.chars.peek() {
+                Some('+') => {
+                    self.chars.next();
+
+                    value = Expression::Add(Box::new(value), Box::new(self.parse_product()?));
+                }
+                Some('-') => {
+                    self.chars.next();
+
+                    value = Expression::Subtract(Box::new(value), Box::new(self.parse_product()?));
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+}
+
+fn parse_value(text: &str) -> Result<TemplatePart, String> {
+    let (body, format) = match text.rsplit_once(':') {
+        Some((body, format)) => (body, format.trim()),
+        None => (text, "d"),
+    };
+
+    let format = match format {
+        "d" => ValueFormat::Signed,
+        "u" => ValueFormat::Unsigned,
+        "x" => ValueFormat::Hex,
+        "b" => ValueFormat::Binary,
+        "c" => ValueFormat::Character,
+        _ => return Err(format!("Unknown format :{}, use d, u, x, b or c", format)),
+    };
+
+    let mut parser = ExpressionParser { chars: body.chars().peekable() };
+
+    let expression = parser.parse_sum()?;
+
+    parser.skip_whitespace();
+
+    if let Some(c) = parser.chars.next() {
+        return Err(format!("Unexpected character '{}' in {{{}}}", c, text));
+    }
+
+    Ok(TemplatePart::Value { expression, format })
+}
+
+impl LogpointTemplate {
+    pub fn parse(text: &str) -> Result<LogpointTemplate, String> {
+        let mut parts = vec![];
+        let mut buffer = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    buffer.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    buffer.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    let mut depth = 0;
+
+                    loop {
+                        match chars.next() {
+                            Some('}') if depth == 0 => break,
+                            Some(c) => {
+                                match c {
+                                    '[' | '(' => depth += 1,
+                                    ']' | ')' => depth -= 1,
+                                    _ => {}
+                                }
+
+                                inner.push(c)
+                            }
+                            None => return Err("Unterminated { in logpoint message".into()),
+                        }
+                    }
+
+                    if !buffer.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut buffer)));
+                    }
+
+                    parts.push(parse_value(&inner)?);
+                }
+                '}' => return Err("Unmatched } in logpoint message, use }} for a brace".into()),
+                _ => buffer.push(c),
+            }
+        }
+
+        if !buffer.is_empty() {
+            parts.push(TemplatePart::Text(buffer));
+        }
+
+        Ok(LogpointTemplate { parts })
+    }
+
+    pub fn format<Mem: Memory>(&self, state: &State<Mem>) -> String {
+        let mut result = String::new();
+
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => result.push_str(text),
+                TemplatePart::Value { expression, format } => {
+                    match evaluate(expression, state) {
+                        Ok(value) => result.push_str(&format_value(value, *format)),
+                        Err(address) => result.push_str(&format!("<unmapped 0x{:08x}>", address)),
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+fn format_value(value: u32, format: ValueFormat) -> String {
+    match format {
+        ValueFormat::Signed => format!("{}", value as i32),
+        ValueFormat::Unsigned => format!("{}", value),
+        ValueFormat::Hex => format!("0x{:x}", value),
+        ValueFormat::Binary => format!("0b{:b}", value),
+        ValueFormat::Character => (value as u8 as char).to_string(),
+    }
+}
+
+// Err holds the address that could not be read.
+fn evaluate<Mem: Memory>(expression: &Expression, state: &State<Mem>) -> Result<u32, u32> {
+    Ok(match expression {
+        Expression::Constant(value) => *value,
+        Expression::Register(Register::Line(index)) => state.registers.line[*index],
+        Expression::Register(Register::Pc) => state.registers.pc,
+        Expression::Register(Register::Hi) => state.registers.hi,
+        Expression::Register(Register::Lo) => state.registers.lo,
+        Expression::Add(a, b) => evaluate(a, state)?.wrapping_add(evaluate(b, state)?),
+        Expression::Subtract(a, b) => evaluate(a, state)?.wrapping_sub(evaluate(b, state)?),
+        Expression::Multiply(a, b) => evaluate(a, state)?.wrapping_mul(evaluate(b, state)?),
+        Expression::Memory { width, address } => {
+            let address = evaluate(address, state)?;
+
+            // Assuming little endian, same as read_display.
+            let mut value = 0u32;
+
+            for i in 0 .. *width {
+                let point = address.wrapping_add(i);
+                let byte = state.memory.get(point).map_err(|_| point)?;
+
+                value |= (byte as u32) << (i * 8);
+            }
+
+            value
+        }
+    })
+}
+
+#[derive(Default)]
+pub struct Logpoints {
+    breakpoints: HashSet<u32>,
+    points: HashMap<u32, LogpointTemplate>,
+}
+
+impl Logpoints {
+    // Returns the set of pcs the executor should stop at (real breakpoints and logpoints).
+    pub fn set_breakpoints(&mut self, breakpoints: HashSet<u32>) -> HashSet<u32> {
+        self.breakpoints = breakpoints;
+
+        self.merged()
+    }
+
+    pub fn set_points(&mut self, points: HashMap<u32, LogpointTemplate>) -> HashSet<u32> {
+        self.points = points;
+
+        self.merged()
+    }
+
+    pub fn merged(&self) -> HashSet<u32> {
+        self.breakpoints
+            .iter()
+            .chain(self.points.keys())
+            .copied()
+            .collect()
+    }
+
+    // A real breakpoint at the same pc wins over the logpoint.
+    pub fn get(&self, pc: u32) -> Option<&LogpointTemplate> {
+        if self.breakpoints.contains(&pc) {
+            None
+        } else {
+            self.points.get(&pc)
+        }
+    }
+}
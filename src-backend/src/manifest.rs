@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use titan::assembler::binary::Binary;
+use titan::cpu::{Memory, State};
+use crate::display::DisplayOptions;
+use crate::layout::{MemoryLayout, MemoryPreset};
+use crate::mmio::DeviceConfig;
+
+// Project settings shared by everyone building the project, read from saturn.toml.
+//
+//   name = "lab3"
+//   files = ["main.asm", "utils.asm"]
+//   entry = "main"
+//
+//   [memory]
+//   preset = "compact_text_at_zero"
+//   stack_size = 0x100000
+//
+//   [display]
+//   address = 0x10008000
+//   width = 64
+//   height = 64
+//
+//   [devices]
+//   enabled = ["keyboard", "timer"]
+//
+//   [run]
+//   args = ["input.txt", "3"]
+//   input = "stdin.txt"
+//   time_travel = true
+pub const MANIFEST_NAME: &str = "saturn.toml";
+
+pub const DEFAULT_STACK_SIZE: u32 = 0x100000;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManifestMemory {
+    // MARS memory configuration, default, compact_data_at_zero or compact_text_at_zero.
+    pub preset: MemoryPreset,
+    // Bytes of stack below the layout's stack base.
+    pub stack_size: Option<u32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ManifestDisplay {
+    pub address: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(flatten)]
+    pub options: DisplayOptions,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManifestRun {
+    // Passed like MARS program arguments, argc in $a0 and argv in $a1.
+    pub args: Vec<String>,
+    // File fed to the program's standard input, relative to the manifest.
+    pub input: Option<String>,
+    // Overrides the front end's time travel setting when set.
+    pub time_travel: Option<bool>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectManifest {
+    pub name: Option<String>,
+    // Source files relative to the manifest, assembled together as a project.
+    pub files: Vec<String>,
+    // Label to start at instead of the binary's entry.
+    pub entry: Option<String>,
+    pub memory: ManifestMemory,
+    pub display: Option<ManifestDisplay>,
+    pub devices: DeviceConfig,
+    pub run: ManifestRun,
+}
+
+impl ProjectManifest {
+    pub fn stack_size(&self) -> u32 {
+        self.memory.stack_size.unwrap_or(DEFAULT_STACK_SIZE)
+    }
+
+    pub fn layout(&self) -> MemoryLayout {
+        MemoryLayout::preset(self.memory.preset)
+    }
+
+    pub fn time_travel(&self, requested: bool) -> bool {
+        self.run.time_travel.unwrap_or(requested)
+    }
+
+    // None keeps the binary's entry, an entry label the binary doesn't define is an error.
+    pub fn entry_address(&self, binary: &Binary) -> Result<Option<u32>, String> {
+        let Some(label) = &self.entry else { return Ok(None) };
+
+        binary.labels
+            .get(label)
+            .map(|address| Some(*address))
+            .ok_or_else(|| format!("Entry label {} is not defined", label))
+    }
+
+    // Sets the entry and program arguments of a freshly loaded state.
+    pub fn setup<Mem: Memory>(&self, state: &mut State<Mem>, entry: Option<u32>) -> Result<(), String> {
+        if let Some(entry) = entry {
+            state.registers.pc = entry
+        }
+
+        if !self.run.args.is_empty() {
+            setup_args(state, &self.run.args)?
+        }
+
+        Ok(())
+    }
+}
+
+// Copies the argument strings and the argv array to the top of the stack, like MARS.
+pub fn setup_args<Mem: Memory>(state: &mut State<Mem>, args: &[String]) -> Result<(), String> {
+    let mut sp = state.registers.line[29];
+    let mut pointers = vec![];
+
+    for arg in args.iter().rev() {
+        sp = u32::try_from(arg.len() + 1)
+            .ok()
+            .and_then(|length| sp.checked_sub(length))
+            .ok_or_else(|| format!("Program argument {} does not fit under the stack pointer", arg))?;
+
+        for (index, byte) in arg.bytes().chain(std::iter::once(0)).enumerate() {
+            state.memory.set(sp + index as u32, byte).ok();
+        }
+
+        pointers.push(sp);
+    }
+
+    sp &= !3;
+
+    // argv is null terminated, $sp ends up a word below it.
+    sp = u32::try_from(4 * (pointers.len() + 1))
+        .ok()
+        .and_then(|length| sp.checked_sub(length))
+        .filter(|sp| *sp >= 4)
+        .ok_or_else(|| "Program arguments do not fit under the stack pointer".to_string())?;
+
+    for (index, pointer) in pointers.iter().rev().chain(std::iter::once(&0)).enumerate() {
+        state.memory.set_u32(sp + 4 * index as u32, *pointer).ok();
+    }
+
+    state.registers.line[4] = args.len() as u32;
+    state.registers.line[5] = sp;
+    state.registers.line[29] = sp - 4;
+
+    Ok(())
+}
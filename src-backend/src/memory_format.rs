@@ -0,0 +1,176 @@
+use std::fmt::Write;
+use byteorder::ByteOrder;
+use crate::hex_format::HexEncoding;
+
+// Memory initialization files for FPGA tools and simulators.
+// Intel HEX and S-records keep the absolute address of each region, $readmemh/$readmemb
+// keep it as an @ word index. COE and MIF describe a single memory, so their addresses
+// start at zero.
+
+const RECORD_BYTES: usize = 16;
+
+// Bits per word, and the words of data in memory order.
+fn words(data: &[u8], encoding: HexEncoding) -> (u32, Vec<u32>) {
+    let read = |f: fn(&[u8]) -> u32| -> Vec<u32> {
+        data.chunks(4)
+            .map(|chunk| {
+                let mut word = chunk.to_vec();
+                word.resize(4, 0);
+
+                f(&word)
+            })
+            .collect()
+    };
+
+    match encoding {
+        HexEncoding::Byte => (8, data.iter().map(|byte| *byte as u32).collect()),
+        HexEncoding::Little32 => (32, read(byteorder::LittleEndian::read_u32)),
+        HexEncoding::Big32 => (32, read(byteorder::BigEndian::read_u32)),
+    }
+}
+
+fn format_word(value: u32, bits: u32, binary: bool) -> String {
+    match (binary, bits) {
+        (true, 8) => format!("{:08b}", value),
+        (true, _) => format!("{:032b}", value),
+        (false, 8) => format!("{:02x}", value),
+        (false, _) => format!("{:08x}", value),
+    }
+}
+
+fn intel_record(kind: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend(data);
+
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    bytes.push(checksum);
+
+    let body: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+    format!(":{}\n", body)
+}
+
+// Intel HEX with extended linear address records, so any 32 bit address can be reached.
+pub fn encode_intel_hex(regions: &[(u32, &[u8])], entry: Option<u32>) -> String {
+    let mut result = String::new();
+    let mut upper: Option<u16> = None;
+
+    for (address, data) in regions {
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let current = address.wrapping_add(offset as u32);
+
+            // Records can't cross a 64KB boundary.
+            let room = 0x10000 - (current & 0xFFFF) as usize;
+            let count = RECORD_BYTES.min(data.len() - offset).min(room);
+
+            let high = (current >> 16) as u16;
+
+            if upper != Some(high) {
+                result.push_str(&intel_record(0x04, 0, &high.to_be_bytes()));
+                upper = Some(high);
+            }
+
+            result.push_str(&intel_record(0x00, current as u16, &data[offset..offset + count]));
+
+            offset += count;
+        }
+    }
+
+    if let Some(entry) = entry {
+        result.push_str(&intel_record(0x05, 0, &entry.to_be_bytes()));
+    }
+
+    result.push_str(&intel_record(0x01, 0, &[]));
+
+    result
+}
+
+fn srecord(kind: u8, address: &[u8], data: &[u8]) -> String {
+    let mut bytes = vec![(address.len() + data.len() + 1) as u8];
+    bytes.extend(address);
+    bytes.extend(data);
+
+    let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    bytes.push(checksum);
+
+    let body: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+    format!("S{}{}\n", kind, body)
+}
+
+// Motorola S-records with 32 bit addresses (S3, terminated by S7).
+pub fn encode_srecord(regions: &[(u32, &[u8])], entry: Option<u32>) -> String {
+    let mut result = srecord(0, &[0, 0], b"saturn");
+    let mut count = 0u32;
+
+    for (address, data) in regions {
+        for (index, chunk) in data.chunks(RECORD_BYTES).enumerate() {
+            let current = address.wrapping_add((index * RECORD_BYTES) as u32);
+
+            result.push_str(&srecord(3, &current.to_be_bytes(), chunk));
+            count += 1;
+        }
+    }
+
+    // Record count, only when it fits S5.
+    if count <= 0xFFFF {
+        result.push_str(&srecord(5, &(count as u16).to_be_bytes(), &[]));
+    }
+
+    result.push_str(&srecord(7, &entry.unwrap_or(0).to_be_bytes(), &[]));
+
+    result
+}
+
+// Verilog $readmemh ($readmemb when binary is set). Addresses are word indices.
+pub fn encode_readmem(regions: &[(u32, &[u8])], encoding: HexEncoding, binary: bool) -> String {
+    let mut result = String::new();
+
+    for (address, data) in regions {
+        let (bits, values) = words(data, encoding);
+
+        writeln!(result, "@{:x}", address / (bits / 8)).ok();
+
+        for line in values.chunks(RECORD_BYTES / (bits as usize / 8)) {
+            let line: Vec<String> = line.iter().map(|value| format_word(*value, bits, binary)).collect();
+
+            writeln!(result, "{}", line.join(" ")).ok();
+        }
+    }
+
+    result
+}
+
+// Xilinx coefficient file, used to initialize block memory.
+pub fn encode_coe(data: &[u8], encoding: HexEncoding) -> String {
+    let (bits, values) = words(data, encoding);
+
+    let vector: Vec<String> = values.iter().map(|value| format_word(*value, bits, false)).collect();
+
+    format!(
+        "memory_initialization_radix=16;\nmemory_initialization_vector=\n{};\n",
+        vector.join(",\n")
+    )
+}
+
+// Altera/Intel memory initialization file.
+pub fn encode_mif(data: &[u8], encoding: HexEncoding) -> String {
+    let (bits, values) = words(data, encoding);
+
+    let mut result = format!(
+        "WIDTH={};\nDEPTH={};\n\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN\n",
+        bits, values.len().max(1)
+    );
+
+    for (index, value) in values.iter().enumerate() {
+        writeln!(result, "    {:x} : {};", index, format_word(*value, bits, false)).ok();
+    }
+
+    result.push_str("END;\n");
+
+    result
+}
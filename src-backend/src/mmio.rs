@@ -0,0 +1,387 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use titan::cpu::memory::section::ListenResponder;
+use titan::cpu::Memory;
+use crate::audio::{AudioChunk, PcmHandler};
+use crate::digital_lab::DigitalLabHandler;
+use crate::display::DisplayWrites;
+use crate::keyboard::{KeyboardHandler, KEYBOARD_ADDRESS};
+use crate::mouse::MouseHandler;
+use crate::syscall::TimeHandler;
+use crate::timer::TimerHandler;
+
+// A memory mapped device in the 0xFFFF page.
+// Devices keep their state behind their own locks, so every method takes &self.
+pub trait MmioDevice: Send + Sync {
+    // Used to enable the device in a project's DeviceConfig and to key its snapshot.
+    fn name(&self) -> &'static str;
+    // Addresses this device answers to, they should not overlap other devices.
+    fn ranges(&self) -> &[Range<u32>];
+
+    fn read(&self, address: u32) -> error::Result<u8>;
+    fn write(&self, address: u32, value: u8) -> error::Result<()>;
+
+    // Called with the number of instructions that ran since the last tick.
+    // Devices are ticked before every access, so their timing is exact to the instruction.
+    fn tick(&self, _cycles: u32) {}
+    // Instructions until the device raises an interrupt or changes on its own, if it's counting down.
+    fn next_event(&self) -> Option<u32> { None }
+    // Cause interrupt lines this device can raise, and the ones pending now.
+    fn lines(&self) -> u32 { 0 }
+    fn interrupts(&self) -> u32 { 0 }
+    // The lines were delivered to the program.
+    fn acknowledge(&self, _lines: u32) {}
+
+    // Called when devices are updated between instructions, now is the wall clock.
+    // Devices that produce output for the front end add it to output.
+    fn update(&self, _memory: &dyn MemoryView, _now: Option<Duration>, _output: &mut DeviceOutput) {}
+    // Wall clock for devices that keep time, attached when execution starts.
+    fn set_clock(&self, _clock: Arc<dyn TimeHandler + Send + Sync>) {}
+
+    // State shown by the front end.
+    fn snapshot(&self) -> Option<serde_json::Value> { None }
+    // Input from the front end, like a key press. The shape is up to the device.
+    fn input(&self, _input: serde_json::Value) -> Result<(), String> {
+        Err(format!("The {} device doesn't take input", self.name()))
+    }
+}
+
+// Program memory, for devices that read from it (like the audio ring buffer).
+pub trait MemoryView {
+    fn view(&self, address: u32) -> Option<u8>;
+}
+
+impl<Mem: Memory> MemoryView for Mem {
+    fn view(&self, address: u32) -> Option<u8> {
+        self.get(address).ok()
+    }
+}
+
+// Collected from the devices on each update.
+#[derive(Default)]
+pub struct DeviceOutput {
+    pub console: String,
+    pub audio: Vec<AudioChunk>,
+}
+
+pub(crate) fn parse_input<T: DeserializeOwned>(input: serde_json::Value) -> Result<T, String> {
+    serde_json::from_value(input).map_err(|error| error.to_string())
+}
+
+// Byte offset of a little endian word register.
+pub(crate) fn word_byte(value: u32, offset: u32) -> u8 {
+    (value >> ((offset % 4) * 8)) as u8
+}
+
+pub(crate) fn set_word_byte(value: &mut u32, offset: u32, byte: u8) {
+    let shift = (offset % 4) * 8;
+
+    *value = (*value & !(0xFF << shift)) | ((byte as u32) << shift)
+}
+
+#[derive(Clone, Default)]
+pub struct DeviceRegistry {
+    devices: Vec<Arc<dyn MmioDevice>>,
+    // Ranges of devices the project turned off, accessing them faults like unmapped memory.
+    disabled: Vec<Range<u32>>,
+    // Instructions executed by the program, advanced by the run loop.
+    clock: Arc<AtomicU64>,
+    // Value of clock the devices were last ticked to.
+    synced: Arc<AtomicU64>,
+    // Set when a device was written to or got input, so its interrupt lines may have changed.
+    changed: Arc<AtomicBool>,
+}
+
+impl DeviceRegistry {
+    pub fn register(&mut self, device: Arc<dyn MmioDevice>) -> Result<(), String> {
+        for range in device.ranges() {
+            let overlap = self.devices
+                .iter()
+                .find(|other| other.ranges().iter().any(|other| overlaps(range, other)));
+
+            if let Some(other) = overlap {
+                return Err(format!(
+                    "The {} device at 0x{:08x} overlaps the {} device",
+                    device.name(), range.start, other.name()
+                ))
+            }
+        }
+
+        self.devices.push(device);
+
+        Ok(())
+    }
+
+    pub fn disable(&mut self, device: &dyn MmioDevice) {
+        self.disabled.extend(device.ranges().iter().cloned())
+    }
+
+    // Device ranges as the program sees them, with the device page at mmio.
+    pub fn ranges_at(&self, mmio: u32) -> Vec<Range<u32>> {
+        let moved = |address: u32| address.wrapping_sub(KEYBOARD_ADDRESS).wrapping_add(mmio);
+
+        self.devices
+            .iter()
+            .flat_map(|device| device.ranges().iter())
+            .map(|range| moved(range.start) .. moved(range.end))
+            .collect()
+    }
+
+    pub fn is_disabled(&self, address: u32) -> bool {
+        self.disabled.iter().any(|range| range.contains(&address))
+    }
+
+    pub fn now(&self) -> u64 {
+        self.clock.load(Ordering::Relaxed)
+    }
+
+    pub fn advance(&self, cycles: u64) {
+        self.clock.fetch_add(cycles, Ordering::Relaxed);
+    }
+
+    // Time travel, devices keep their state but the clock goes back.
+    pub fn rewind(&self, cycles: u64) {
+        let now = self.now().saturating_sub(cycles);
+
+        self.clock.store(now, Ordering::Relaxed);
+        self.synced.fetch_min(now, Ordering::Relaxed);
+    }
+
+    // Ticks every device up to the clock.
+    pub fn sync(&self) {
+        let now = self.now();
+        let last = self.synced.swap(now, Ordering::Relaxed);
+
+        if now > last {
+            self.tick((now - last).min(u32::MAX as u64) as u32)
+        }
+    }
+
+    pub fn notify(&self) {
+        self.changed.store(true, Ordering::Relaxed)
+    }
+
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+
+    fn find(&self, address: u32) -> Option<&Arc<dyn MmioDevice>> {
+        self.devices
+            .iter()
+            .find(|device| device.ranges().iter().any(|range| range.contains(&address)))
+    }
+
+    fn tick(&self, cycles: u32) {
+        for device in &self.devices {
+            device.tick(cycles)
+        }
+    }
+
+    pub fn next_event(&self) -> Option<u32> {
+        self.devices.iter().filter_map(|device| device.next_event()).min()
+    }
+
+    pub fn lines(&self) -> u32 {
+        self.devices.iter().fold(0, |lines, device| lines | device.lines())
+    }
+
+    pub fn interrupts(&self) -> u32 {
+        self.devices.iter().fold(0, |lines, device| lines | device.interrupts())
+    }
+
+    pub fn acknowledge(&self, lines: u32) {
+        for device in &self.devices {
+            device.acknowledge(lines & device.lines())
+        }
+    }
+
+    pub fn update(&self, memory: &dyn MemoryView, now: Option<Duration>) -> DeviceOutput {
+        let mut output = DeviceOutput::default();
+
+        for device in &self.devices {
+            device.update(memory, now, &mut output)
+        }
+
+        output
+    }
+
+    pub fn set_clock(&self, clock: Arc<dyn TimeHandler + Send + Sync>) {
+        for device in &self.devices {
+            device.set_clock(clock.clone())
+        }
+    }
+
+    pub fn input(&self, name: &str, input: serde_json::Value) -> Result<(), String> {
+        let device = self.devices
+            .iter()
+            .find(|device| device.name() == name)
+            .ok_or_else(|| format!("No {} device is mounted", name))?;
+
+        device.input(input)?;
+
+        self.notify();
+
+        Ok(())
+    }
+
+    pub fn snapshots(&self) -> HashMap<String, serde_json::Value> {
+        self.devices
+            .iter()
+            .filter_map(|device| Some((device.name().to_string(), device.snapshot()?)))
+            .collect()
+    }
+}
+
+// Per project device settings, missing fields keep the defaults.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    // Names of the devices to mount, every built in device if missing.
+    pub enabled: Option<Vec<String>>,
+    pub transmit_delay: Option<u32>,
+}
+
+pub type DeviceConfigBody = Arc<Mutex<DeviceConfig>>;
+
+impl DeviceConfig {
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled
+            .as_ref()
+            .map(|enabled| enabled.iter().any(|item| item == name))
+            .unwrap_or(true)
+    }
+}
+
+fn overlaps(a: &Range<u32>, b: &Range<u32>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+// Devices mounted for a run, shared with the executor and the front end.
+#[derive(Clone)]
+pub struct MmioDevices {
+    pub registry: DeviceRegistry,
+}
+
+// Every device Saturn ships with, configured for the project.
+pub fn builtin_devices(config: &DeviceConfig) -> Vec<Arc<dyn MmioDevice>> {
+    let keyboard = KeyboardHandler::new();
+
+    if let Some(delay) = config.transmit_delay {
+        keyboard.state.lock().unwrap().set_transmit_delay(delay);
+    }
+
+    vec![
+        Arc::new(keyboard),
+        Arc::new(DigitalLabHandler::default()),
+        Arc::new(MouseHandler::default()),
+        Arc::new(PcmHandler::default()),
+        Arc::new(TimerHandler::default()),
+    ]
+}
+
+impl MmioDevices {
+    // Built in devices, filtered by the project's config.
+    pub fn new(config: &DeviceConfig) -> MmioDevices {
+        MmioDevices::with_devices(config, builtin_devices(config))
+            .expect("Built in devices should not overlap")
+    }
+
+    // Devices the project's config leaves out keep their ranges unmapped.
+    pub fn with_devices(config: &DeviceConfig, devices: Vec<Arc<dyn MmioDevice>>) -> Result<MmioDevices, String> {
+        let mut registry = DeviceRegistry::default();
+
+        for device in devices {
+            if config.is_enabled(device.name()) {
+                registry.register(device)?
+            } else {
+                registry.disable(device.as_ref())
+            }
+        }
+
+        Ok(MmioDevices { registry })
+    }
+}
+
+// Backs a 64KB section with RAM.
+// In the device page, addresses claimed by registered devices are routed to them.
+// Unclaimed addresses behave like RAM, same as MARS.
+// Sections under the display also record which of its rows were written.
+pub struct MmioHandler {
+    registry: Option<DeviceRegistry>,
+    // Start of the device page, devices see it at KEYBOARD_ADDRESS.
+    mmio: u32,
+    base: u32,
+    memory: Vec<u8>,
+    writes: Option<DisplayWrites>,
+}
+
+impl MmioHandler {
+    // Takes over the section at base with its current contents.
+    // Registry is set for the section holding the device page.
+    pub fn new(
+        registry: Option<DeviceRegistry>, mmio: u32, base: u32, memory: Vec<u8>, writes: Option<DisplayWrites>
+    ) -> MmioHandler {
+        MmioHandler { registry, mmio, base, memory, writes }
+    }
+
+    fn offset(&self, address: u32) -> usize {
+        (address.wrapping_sub(self.base) & 0xFFFF) as usize
+    }
+
+    // The registry and the address as devices see it, for addresses in the device page.
+    fn device(&self, address: u32) -> Option<(&DeviceRegistry, u32)> {
+        let registry = self.registry.as_ref()?;
+        let offset = address.checked_sub(self.mmio).filter(|offset| *offset < 0x10000)?;
+
+        Some((registry, KEYBOARD_ADDRESS + offset))
+    }
+}
+
+impl ListenResponder for MmioHandler {
+    fn read(&self, address: u32) -> error::Result<u8> {
+        if let Some((registry, device_address)) = self.device(address) {
+            registry.sync();
+
+            if let Some(device) = registry.find(device_address) {
+                return device.read(device_address).map_err(|_| MemoryUnmapped(address))
+            }
+
+            if registry.is_disabled(device_address) {
+                return Err(MemoryUnmapped(address))
+            }
+        }
+
+        Ok(self.memory[self.offset(address)])
+    }
+
+    fn write(&mut self, address: u32, value: u8) -> error::Result<()> {
+        if let Some((registry, device_address)) = self.device(address) {
+            registry.sync();
+            registry.notify();
+
+            if let Some(device) = registry.find(device_address) {
+                return device.write(device_address, value).map_err(|_| MemoryUnmapped(address))
+            }
+
+            if registry.is_disabled(device_address) {
+                return Err(MemoryUnmapped(address))
+            }
+        }
+
+        let offset = self.offset(address);
+        self.memory[offset] = value;
+
+        if let Some(writes) = &self.writes {
+            writes.mark(address)
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use crate::exception::INTERRUPT_MOUSE;
+use crate::mmio::{parse_input, word_byte, MmioDevice};
+
+//   0xFFFF0040 cursor x in display units
+//   0xFFFF0044 cursor y in display units
+//   0xFFFF0048 buttons held (MOUSE_LEFT, MOUSE_RIGHT, MOUSE_MIDDLE)
+//   0xFFFF004C byte 0 is the number of queued clicks, byte 1 is 1 to interrupt while clicks are queued
+//   0xFFFF0050 reading byte 0 takes the next click: the button bit, with MOUSE_RELEASED set on release, 0 if none
+//   0xFFFF0054 x of the last taken click
+//   0xFFFF0058 y of the last taken click
+pub const MOUSE_ADDRESS: u32 = 0xFFFF0040;
+pub const MOUSE_END: u32 = 0xFFFF005C;
+const MOUSE_RANGE: Range<u32> = MOUSE_ADDRESS..MOUSE_END;
+
+pub const MOUSE_LEFT: u8 = 0b001;
+pub const MOUSE_RIGHT: u8 = 0b010;
+pub const MOUSE_MIDDLE: u8 = 0b100;
+pub const MOUSE_RELEASED: u8 = 0x80;
+
+// Clicks past this are dropped.
+pub const MOUSE_QUEUE_SIZE: usize = 64;
+
+#[derive(Copy, Clone, Default)]
+struct MouseClick {
+    event: u8,
+    x: u32,
+    y: u32,
+}
+
+#[derive(Default)]
+pub struct MouseState {
+    x: u32,
+    y: u32,
+    buttons: u8,
+    clicks: VecDeque<MouseClick>,
+    last: MouseClick,
+    interrupt_enabled: bool,
+}
+
+// Cursor in display units, buttons is a mask of MOUSE_LEFT, MOUSE_RIGHT and MOUSE_MIDDLE.
+#[derive(Deserialize)]
+struct MouseInput {
+    x: u32,
+    y: u32,
+    buttons: u8,
+}
+
+#[derive(Default)]
+pub struct MouseHandler {
+    pub state: Arc<Mutex<MouseState>>,
+}
+
+impl MouseState {
+    // Queues a click for every button that changed since the last post.
+    pub fn post(&mut self, x: u32, y: u32, buttons: u8) {
+        self.x = x;
+        self.y = y;
+
+        let changed = self.buttons ^ buttons;
+
+        for button in [MOUSE_LEFT, MOUSE_RIGHT, MOUSE_MIDDLE] {
+            if changed & button == 0 || self.clicks.len() >= MOUSE_QUEUE_SIZE {
+                continue
+            }
+
+            let released = if buttons & button == 0 { MOUSE_RELEASED } else { 0 };
+
+            self.clicks.push_back(MouseClick { event: button | released, x, y })
+        }
+
+        self.buttons = buttons;
+    }
+
+    fn take_click(&mut self) -> u8 {
+        self.last = self.clicks.pop_front().unwrap_or_default();
+
+        self.last.event
+    }
+}
+
+impl MmioDevice for MouseHandler {
+    fn name(&self) -> &'static str {
+        "mouse"
+    }
+
+    fn ranges(&self) -> &[Range<u32>] {
+        std::slice::from_ref(&MOUSE_RANGE)
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let mut state = self.state.lock().unwrap();
+
+        let offset = address.wrapping_sub(MOUSE_ADDRESS);
+
+        Ok(match offset {
+            0x0..=0x3 => word_byte(state.x, offset),
+            0x4..=0x7 => word_byte(state.y, offset),
+            0x8..=0xB => word_byte(state.buttons as u32, offset),
+            0xC => state.clicks.len() as u8,
+            0xD => state.interrupt_enabled as u8,
+            0x10 => state.take_click(),
+            0x14..=0x17 => word_byte(state.last.x, offset),
+            0x18..=0x1B => word_byte(state.last.y, offset),
+            0xE..=0xF | 0x11..=0x13 => 0,
+            _ => return Err(MemoryUnmapped(address))
+        })
+    }
+
+    fn write(&self, address: u32, value: u8) -> error::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        match address.wrapping_sub(MOUSE_ADDRESS) {
+            0xD => state.interrupt_enabled = value & 1 != 0,
+            // Everything else is read only.
+            0x0..=0x1B => {}
+            _ => return Err(MemoryUnmapped(address))
+        }
+
+        Ok(())
+    }
+
+    fn lines(&self) -> u32 {
+        INTERRUPT_MOUSE
+    }
+
+    // Level triggered like the keyboard, pending until every click is taken.
+    fn interrupts(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+
+        if state.interrupt_enabled && !state.clicks.is_empty() {
+            INTERRUPT_MOUSE
+        } else {
+            0
+        }
+    }
+
+    fn input(&self, input: serde_json::Value) -> Result<(), String> {
+        let input: MouseInput = parse_input(input)?;
+
+        self.state.lock().unwrap().post(input.x, input.y, input.buttons);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use titan::assembler::binary::Binary;
+use crate::build::{assemble_text, Breakpoint};
+use crate::diagnostics::{collect_errors, collect_warnings, Diagnostic, Severity, SourcePosition};
+use crate::layout::MemoryLayout;
+use crate::symbols::{global_declarations, identifiers, scan_definitions, symbol_table, Symbol};
+
+// One source file of a multi-file project, like MARS "assemble all files in directory".
+#[derive(Clone, Deserialize)]
+pub struct ProjectFile {
+    pub path: String,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status")]
+pub enum ProjectResult {
+    Error {
+        diagnostics: Vec<Diagnostic>,
+    },
+    Success {
+        // Keyed by file path.
+        breakpoints: HashMap<String, Vec<Breakpoint>>,
+        symbols: Vec<Symbol>,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+// A replacement made in a file before it's joined, like a renamed label.
+struct Edit {
+    line: usize,
+    column: usize,
+    old: String,
+    new: String,
+}
+
+// Where each file starts in the combined source.
+struct FileSpan {
+    path: String,
+    // Line of the file's first line, after the .text line put before it.
+    start: usize,
+    lines: usize,
+    // Sorted by line, then column.
+    edits: Vec<Edit>,
+}
+
+impl FileSpan {
+    // Maps a column on an edited line back to the file, a column inside an edit maps to its start.
+    fn column(&self, line: usize, column: usize) -> usize {
+        let mut shift = 0isize;
+
+        for edit in self.edits.iter().filter(|edit| edit.line == line) {
+            let start = edit.column as isize + shift;
+
+            if (column as isize) < start {
+                break
+            }
+
+            if (column as isize) < start + edit.new.len() as isize {
+                return edit.column
+            }
+
+            shift += edit.new.len() as isize - edit.old.len() as isize;
+        }
+
+        (column as isize - shift).max(0) as usize
+    }
+
+    // Puts back the names the file used.
+    fn message(&self, message: &str) -> String {
+        self.edits
+            .iter()
+            .fold(message.to_string(), |message, edit| message.replace(&edit.new, &edit.old))
+    }
+}
+
+struct SourceMap {
+    spans: Vec<FileSpan>,
+}
+
+impl SourceMap {
+    fn span(&self, line: usize) -> Option<&FileSpan> {
+        self.spans
+            .iter()
+            .find(|span| span.start <= line && line < span.start + span.lines)
+    }
+
+    // Maps a combined line back to its file, None for the lines added between files.
+    fn find(&self, line: usize) -> Option<(&str, usize)> {
+        self.span(line).map(|span| (span.path.as_str(), line - span.start))
+    }
+
+    fn map_position(&self, position: SourcePosition) -> Option<(&str, SourcePosition)> {
+        let span = self.span(position.line)?;
+        let line = position.line - span.start;
+
+        Some((span.path.as_str(), SourcePosition { line, column: span.column(line, position.column) }))
+    }
+
+    fn map_diagnostic(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        let Some(span) = self.span(diagnostic.start.line) else { return diagnostic };
+        let line = diagnostic.start.line - span.start;
+
+        let end = if diagnostic.end.line == diagnostic.start.line {
+            span.column(line, diagnostic.end.column)
+        } else {
+            diagnostic.end.column
+        };
+
+        diagnostic.path = Some(span.path.clone());
+        diagnostic.message = span.message(&diagnostic.message);
+        diagnostic.end.line = diagnostic.end.line - diagnostic.start.line + line;
+        diagnostic.end.column = end.max(span.column(line, diagnostic.start.column));
+        diagnostic.start = SourcePosition { line, column: span.column(line, diagnostic.start.column) };
+
+        diagnostic
+    }
+}
+
+// A label reference, an identifier after the mnemonic (or directive) of a line.
+struct Reference {
+    name: String,
+    position: SourcePosition,
+    // Operand of an instruction, not a directive.
+    instruction: bool,
+}
+
+fn references(text: &str) -> Vec<Reference> {
+    let mut result = vec![];
+    let mut in_macro = false;
+
+    for (line, code) in text.split('\n').enumerate() {
+        // Skip label definitions, then the mnemonic.
+        let mut words = identifiers(code)
+            .into_iter()
+            .skip_while(|(column, name)| code[column + name.len()..].trim_start().starts_with(':'));
+
+        let Some((_, mnemonic)) = words.next() else { continue };
+
+        match mnemonic {
+            ".macro" => in_macro = true,
+            ".end_macro" => in_macro = false,
+            _ => {}
+        }
+
+        // Macro bodies only make sense once expanded.
+        if in_macro {
+            continue
+        }
+
+        for (column, name) in words {
+            result.push(Reference {
+                name: name.to_string(),
+                position: SourcePosition { line, column },
+                instruction: !mnemonic.starts_with('.'),
+            })
+        }
+    }
+
+    result
+}
+
+// Names given a value with .eqv, which aren't labels.
+fn equivalences(text: &str) -> Vec<String> {
+    text.split('\n')
+        .filter_map(|code| {
+            let mut words = identifiers(code).into_iter();
+
+            match (words.next(), words.next()) {
+                (Some((_, ".eqv")), Some((_, name))) => Some(name.to_string()),
+                _ => None
+            }
+        })
+        .collect()
+}
+
+// The path of an .include on the line, with the column it starts at.
+fn include_path(code: &str) -> Option<(usize, &str)> {
+    let Some((_, ".include")) = identifiers(code).into_iter().next() else { return None };
+
+    let open = code.find('"')? + 1;
+    let close = open + code[open..].find('"')?;
+
+    Some((open, &code[open..close]))
+}
+
+// Renames labels and points relative includes at the file's own directory,
+// since the joined source only resolves includes from one path.
+fn edit_file(text: &str, names: &HashMap<String, String>, directory: Option<&Path>) -> (String, Vec<Edit>) {
+    let mut edits = vec![];
+
+    for (line, code) in text.split('\n').enumerate() {
+        if let Some((column, path)) = include_path(code) {
+            if let Some(directory) = directory.filter(|_| Path::new(path).is_relative()) {
+                edits.push(Edit {
+                    line,
+                    column,
+                    old: path.to_string(),
+                    new: directory.join(path).to_string_lossy().into_owned(),
+                });
+            }
+
+            continue
+        }
+
+        for (column, name) in identifiers(code) {
+            if let Some(replacement) = names.get(name) {
+                edits.push(Edit { line, column, old: name.to_string(), new: replacement.clone() })
+            }
+        }
+    }
+
+    if edits.is_empty() {
+        return (text.to_string(), edits)
+    }
+
+    let result = text.split('\n')
+        .enumerate()
+        .map(|(line, code)| {
+            let mut result = String::new();
+            let mut last = 0;
+
+            for edit in edits.iter().filter(|edit| edit.line == line) {
+                result.push_str(&code[last..edit.column]);
+                result.push_str(&edit.new);
+                last = edit.column + edit.old.len();
+            }
+
+            result.push_str(&code[last..]);
+
+            result
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (result, edits)
+}
+
+fn link_error(path: &str, position: SourcePosition, length: usize, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        message,
+        path: Some(path.to_string()),
+        start: position,
+        end: SourcePosition { line: position.line, column: position.column + length },
+        fixes: vec![],
+    }
+}
+
+// Labels are local to their file unless a file declares them with .globl.
+// Files are joined into one source for the assembler, so local labels defined by
+// more than one file are renamed first. Returns the combined source, its line map,
+// the renamed labels (new name to old name) and any linking errors.
+fn link(files: &[ProjectFile], header: &str) -> (String, SourceMap, HashMap<String, String>, Vec<Diagnostic>) {
+    let definitions: Vec<_> = files.iter().map(|file| scan_definitions(&file.text)).collect();
+    let globals: Vec<_> = files.iter().map(|file| global_declarations(&file.text)).collect();
+
+    let global_sets: Vec<HashSet<&str>> = globals
+        .iter()
+        .map(|list| list.iter().map(|(name, _)| name.as_str()).collect())
+        .collect();
+
+    let mut errors = vec![];
+
+    // Every file defining a label, and which of those export it.
+    let mut defined_in: HashMap<&str, Vec<usize>> = HashMap::new();
+
+    for (index, labels) in definitions.iter().enumerate() {
+        for name in labels.keys() {
+            defined_in.entry(name.as_str()).or_default().push(index)
+        }
+    }
+
+    let exported_by = |name: &str| -> Vec<usize> {
+        defined_in
+            .get(name)
+            .map(|files| files.iter().copied().filter(|file| global_sets[*file].contains(name)).collect())
+            .unwrap_or_default()
+    };
+
+    for (index, declarations) in globals.iter().enumerate() {
+        for (name, position) in declarations {
+            let exporters = exported_by(name);
+
+            if !defined_in.contains_key(name.as_str()) {
+                errors.push(link_error(
+                    &files[index].path, *position, name.len(),
+                    format!("Global symbol {} is not defined in any file", name),
+                ))
+            } else if exporters.len() > 1 && exporters.contains(&index) && exporters[0] != index {
+                errors.push(link_error(
+                    &files[index].path, *position, name.len(),
+                    format!("Global symbol {} is also defined in {}", name, files[exporters[0]].path),
+                ))
+            }
+        }
+    }
+
+    let equivalences: HashSet<String> = files.iter().flat_map(|file| equivalences(&file.text)).collect();
+
+    // Labels from an included file aren't known here, so undefined labels are left to the assembler.
+    let includes = files.iter().any(|file| file.text.split('\n').any(|code| include_path(code).is_some()));
+
+    // Labels used in a file that only another file defines without exporting it, or that no file defines.
+    for (index, file) in files.iter().enumerate() {
+        for Reference { name, position, instruction } in references(&file.text) {
+            if definitions[index].contains_key(&name) || !exported_by(&name).is_empty() {
+                continue
+            }
+
+            let message = match defined_in.get(name.as_str()).and_then(|files| files.first()) {
+                Some(owner) => format!("{} is defined in {} but not declared with .globl", name, files[*owner].path),
+                None if instruction && !includes && !equivalences.contains(&name) => {
+                    format!("Label {} is not defined in any file", name)
+                }
+                None => continue
+            };
+
+            errors.push(link_error(&file.path, position, name.len(), message))
+        }
+    }
+
+    let mut renamed = HashMap::new();
+    let mut combined = header.to_string();
+    let mut spans = vec![];
+    let mut line = header.matches('\n').count();
+
+    for (index, file) in files.iter().enumerate() {
+        let names: HashMap<String, String> = definitions[index]
+            .keys()
+            .filter(|name| !global_sets[index].contains(name.as_str()))
+            .filter(|name| defined_in.get(name.as_str()).map(|files| files.len() > 1).unwrap_or(false))
+            .map(|name| (name.clone(), format!("{}__file{}", name, index)))
+            .collect();
+
+        for (old, new) in &names {
+            renamed.insert(new.clone(), old.clone());
+        }
+
+        let directory = Path::new(&file.path).parent().filter(|directory| !directory.as_os_str().is_empty());
+
+        let (text, edits) = edit_file(&file.text, &names, directory);
+        let lines = text.split('\n').count();
+
+        // Every file starts in .text, same as MARS.
+        combined.push_str(".text\n");
+        combined.push_str(&text);
+        combined.push('\n');
+
+        spans.push(FileSpan { path: file.path.clone(), start: line + 1, lines, edits });
+
+        line += lines + 1;
+    }
+
+    (combined, SourceMap { spans }, renamed, errors)
+}
+
+pub fn assemble_project(files: &[ProjectFile], layout: &MemoryLayout) -> (Option<Binary>, ProjectResult) {
+    // Segment addresses for layouts other than the assembler's own.
+    let header = if layout.is_default() { String::new() } else { layout.section_header() };
+
+    let (source, map, renamed, errors) = link(files, &header);
+
+    let warnings: Vec<Diagnostic> = files
+        .iter()
+        .flat_map(|file| collect_warnings(&file.text, Some(&file.path)))
+        .collect();
+
+    if !errors.is_empty() {
+        let mut diagnostics = errors;
+        diagnostics.extend(warnings);
+
+        return (None, ProjectResult::Error { diagnostics })
+    }
+
+    // Relative includes were already pointed at each file's directory.
+    let path = files.first().map(|file| file.path.as_str());
+
+    let binary = match assemble_text(&source, path) {
+        Ok(binary) => binary,
+        Err(error) => {
+            let mut diagnostics: Vec<Diagnostic> = collect_errors(&source, path, &error)
+                .into_iter()
+                .map(|diagnostic| map.map_diagnostic(diagnostic))
+                .collect();
+
+            diagnostics.extend(warnings);
+
+            return (None, ProjectResult::Error { diagnostics })
+        }
+    };
+
+    let mut breakpoints: HashMap<String, Vec<Breakpoint>> = HashMap::new();
+
+    for breakpoint in binary.source_breakpoints(&source, 0) {
+        if let Some((path, line)) = map.find(breakpoint.line) {
+            breakpoints
+                .entry(path.to_string())
+                .or_default()
+                .push(Breakpoint { line, pcs: breakpoint.pcs })
+        }
+    }
+
+    let symbols = symbol_table(&binary, &source)
+        .into_iter()
+        .map(|mut symbol| {
+            if let Some(old) = renamed.get(&symbol.name) {
+                symbol.name = old.clone();
+            }
+
+            if let Some(location) = &mut symbol.location {
+                if let Some((path, position)) = map.map_position(*location) {
+                    symbol.path = Some(path.to_string());
+                    *location = position;
+                }
+            }
+
+            symbol
+        })
+        .collect();
+
+    (Some(binary), ProjectResult::Success { breakpoints, symbols, diagnostics: warnings })
+}
@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use crate::hex_format::{decode_hex_with_encoding, HexEncoding};
+
+#[derive(Copy, Clone, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+// How the bytes handed to disassemble are laid out.
+#[derive(Clone, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BinaryFormat {
+    #[default]
+    Elf,
+    // A memory image (.bin) starting at base.
+    Raw { base: u32, endian: Endianness },
+    // Logisim "v3.0 hex words plain" text, like export_hex_regions writes.
+    Hex { base: u32, encoding: HexEncoding },
+}
+
+const ELF_HEADER_SIZE: u32 = 0x34;
+const PROGRAM_HEADER_SIZE: u32 = 0x20;
+
+const ET_EXEC: u16 = 2;
+const EM_MIPS: u16 = 8;
+const PT_LOAD: u32 = 1;
+// Readable and executable.
+const PF_RX: u32 = 0x5;
+
+// Wraps a little endian memory image in an ELF with a single executable segment at base,
+// so it goes through the same inspection as real ELF files.
+pub fn image_elf(data: &[u8], base: u32) -> Vec<u8> {
+    let mut out = b"\x7FELF".to_vec();
+
+    // 32 bit, little endian, version 1.
+    out.extend([1, 1, 1]);
+    out.resize(16, 0);
+
+    out.extend(ET_EXEC.to_le_bytes());
+    out.extend(EM_MIPS.to_le_bytes());
+
+    for value in [1, base, ELF_HEADER_SIZE, 0, 0] {
+        out.extend(u32::to_le_bytes(value))
+    }
+
+    for value in [ELF_HEADER_SIZE as u16, PROGRAM_HEADER_SIZE as u16, 1, 0, 0, 0] {
+        out.extend(u16::to_le_bytes(value))
+    }
+
+    let offset = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE;
+    let size = data.len() as u32;
+
+    for value in [PT_LOAD, offset, base, base, size, size, PF_RX, 4] {
+        out.extend(u32::to_le_bytes(value))
+    }
+
+    out.extend(data);
+
+    out
+}
+
+// Converts any supported input to ELF bytes.
+pub fn binary_elf(bytes: Vec<u8>, format: &BinaryFormat) -> Result<Vec<u8>, String> {
+    match format {
+        BinaryFormat::Elf => Ok(bytes),
+        BinaryFormat::Raw { base, endian: Endianness::Little } => Ok(image_elf(&bytes, *base)),
+        BinaryFormat::Raw { base, endian: Endianness::Big } => {
+            // Memory is little endian, swap each word. A trailing partial word is padded.
+            let data: Vec<u8> = bytes
+                .chunks(4)
+                .flat_map(|word| {
+                    let mut word = word.to_vec();
+                    word.resize(4, 0);
+                    word.reverse();
+
+                    word
+                })
+                .collect();
+
+            Ok(image_elf(&data, *base))
+        }
+        BinaryFormat::Hex { base, encoding } => {
+            let text = String::from_utf8(bytes).map_err(|_| "Hex file is not valid text".to_string())?;
+
+            Ok(image_elf(&decode_hex_with_encoding(&text, *encoding)?, *base))
+        }
+    }
+}
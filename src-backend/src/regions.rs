@@ -1,28 +1,67 @@
 use serde::{Deserialize, Serialize};
 use titan::assembler::binary::{RawRegion, RegionFlags};
-use crate::build::{assemble_text, AssemblerResult};
+use crate::build::{assemble_layout, AssemblerResult};
+use crate::layout::MemoryLayout;
 use crate::hex_format::{encode_hex_with_encoding, HexEncoding};
+use crate::memory_format::{encode_coe, encode_intel_hex, encode_mif, encode_readmem, encode_srecord};
 use titan::assembler::binary::Binary;
 use base64::Engine;
 
+fn default_extension() -> String {
+    "txt".to_string()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct HexRegion {
     pub name: String,
-    pub data: String // base64 encoded
+    pub data: String, // base64 encoded
+    #[serde(default = "default_extension")]
+    pub extension: String
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
 #[serde(rename_all="snake_case")]
 pub enum AssembleRegionsKind {
     Plain,
-    HexV3
+    HexV3,
+    IntelHex,
+    Srec,
+    Readmemh,
+    Readmemb,
+    Coe,
+    Mif
+}
+
+impl AssembleRegionsKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AssembleRegionsKind::Plain => "bin",
+            AssembleRegionsKind::HexV3 => "txt",
+            AssembleRegionsKind::IntelHex => "hex",
+            AssembleRegionsKind::Srec => "srec",
+            AssembleRegionsKind::Readmemh | AssembleRegionsKind::Readmemb => "mem",
+            AssembleRegionsKind::Coe => "coe",
+            AssembleRegionsKind::Mif => "mif",
+        }
+    }
 }
 
+// Continuous images bigger than this are rejected instead of allocated.
+pub const MAX_CONTINUOUS_SIZE: u64 = 16 * 1024 * 1024;
+
 #[derive(Serialize, Deserialize)]
 pub struct AssembleRegionsOptions {
     pub kind: AssembleRegionsKind,
     pub continuous: bool,
-    pub encoding: HexEncoding // Encoding option ignored if kind != HexV3
+    pub encoding: HexEncoding, // Word size for HexV3, $readmem, COE and MIF, ignored by the rest
+    // Bounds of a continuous image, end is exclusive. Default to the lowest and highest region addresses.
+    #[serde(default)]
+    pub start: Option<u32>,
+    #[serde(default)]
+    pub end: Option<u32>,
+    // Fills the gaps between regions.
+    #[serde(default)]
+    pub fill: u8
 }
 
 #[derive(Serialize)]
@@ -58,38 +97,75 @@ fn region_name(region: &RawRegion, entry: bool) -> String {
     format!("{heading}_{address:x}_{flags}")
 }
 
-fn encode_region_data(data: &[u8], options: &AssembleRegionsOptions) -> String {
-    match options.kind {
-        AssembleRegionsKind::HexV3 => {
-            let encoding = encode_hex_with_encoding(data, options.encoding);
+// address and entry are kept by the formats that can record them.
+fn encode_region_data(data: &[u8], address: u32, entry: Option<u32>, options: &AssembleRegionsOptions) -> String {
+    let regions = [(address, data)];
 
-            base64::engine::general_purpose::STANDARD.encode(encoding)
-        },
+    let encoded = match options.kind {
+        AssembleRegionsKind::HexV3 => encode_hex_with_encoding(data, options.encoding),
         AssembleRegionsKind::Plain => {
-            base64::engine::general_purpose::STANDARD.encode(data)
+            return base64::engine::general_purpose::STANDARD.encode(data)
         }
-    }
+        AssembleRegionsKind::IntelHex => encode_intel_hex(&regions, entry),
+        AssembleRegionsKind::Srec => encode_srecord(&regions, entry),
+        AssembleRegionsKind::Readmemh => encode_readmem(&regions, options.encoding, false),
+        AssembleRegionsKind::Readmemb => encode_readmem(&regions, options.encoding, true),
+        AssembleRegionsKind::Coe => encode_coe(data, options.encoding),
+        AssembleRegionsKind::Mif => encode_mif(data, options.encoding),
+    };
+
+    base64::engine::general_purpose::STANDARD.encode(encoded)
 }
 
-pub fn export_continuous(binary: &Binary, options: &AssembleRegionsOptions) -> String {
-    let mut output: Vec<u8> = vec![];
+// One image covering start to end, relative to start. Regions outside the bounds are cut off.
+pub fn export_continuous(binary: &Binary, options: &AssembleRegionsOptions) -> Result<String, String> {
+    let regions: Vec<&RawRegion> = binary.regions
+        .iter()
+        .filter(|region| !region.data.is_empty())
+        .collect();
+
+    let start = options.start
+        .map(|start| start as u64)
+        .or_else(|| regions.iter().map(|region| region.address as u64).min())
+        .unwrap_or(0);
+
+    let end = options.end
+        .map(|end| end as u64)
+        .or_else(|| regions.iter().map(|region| region.address as u64 + region.data.len() as u64).max())
+        .unwrap_or(start);
+
+    if end < start {
+        return Err(format!("Export end 0x{:08x} is before start 0x{:08x}", end, start))
+    }
+
+    let size = end - start;
 
-    for region in &binary.regions {
-        if region.data.is_empty() {
-            continue
-        }
+    if size > MAX_CONTINUOUS_SIZE {
+        return Err(format!(
+            "Continuous export from 0x{:08x} to 0x{:08x} would be {} bytes, over the {} byte limit. \
+            Set start and end bounds, or export regions separately",
+            start, end, size, MAX_CONTINUOUS_SIZE
+        ))
+    }
+
+    let mut output = vec![options.fill; size as usize];
 
-        // Potential Overflow!
-        let end = region.address as usize + region.data.len();
+    for region in regions {
+        let region_start = region.address as u64;
+        let region_end = region_start + region.data.len() as u64;
 
-        if end > output.len() {
-            output.resize(end, 0);
+        let from = region_start.max(start);
+        let to = region_end.min(end);
+
+        if from >= to {
+            continue
         }
 
-        output[region.address as usize .. end].copy_from_slice(&region.data);
-    };
+        output[(from - start) as usize .. (to - start) as usize]
+            .copy_from_slice(&region.data[(from - region_start) as usize .. (to - region_start) as usize]);
+    }
 
-    encode_region_data(&output, options)
+    Ok(encode_region_data(&output, start as u32, Some(binary.entry), options))
 }
 
 fn export_regions(binary: &Binary, options: &AssembleRegionsOptions) -> Vec<HexRegion> {
@@ -98,27 +174,33 @@ fn export_regions(binary: &Binary, options: &AssembleRegionsOptions) -> Vec<HexR
             return None
         }
 
-        let name = region_name(region, region.address == binary.entry);
+        let entry = region.address == binary.entry;
+        let name = region_name(region, entry);
 
         Some(HexRegion {
             name,
-            data: encode_region_data(&region.data, options),
+            data: encode_region_data(&region.data, region.address, entry.then_some(binary.entry), options),
+            extension: options.kind.extension().to_string(),
         })
     }).collect()
 }
 
 pub fn assemble_regions(
-    text: &str, path: Option<&str>, options: AssembleRegionsOptions
+    text: &str, path: Option<&str>, options: AssembleRegionsOptions, layout: &MemoryLayout
 ) -> (Option<AssembledRegions>, AssemblerResult) {
-    let result = assemble_text(text, path);
-    let (binary, result) = AssemblerResult::from_result_with_binary(result, text);
+    let (binary, result) = assemble_layout(text, path, layout);
 
     let Some(binary) = binary else {
         return (None, result)
     };
 
     let regions = if options.continuous {
-        AssembledRegions::Binary(export_continuous(&binary, &options))
+        match export_continuous(&binary, &options) {
+            Ok(data) => AssembledRegions::Binary(data),
+            Err(message) => {
+                return (None, AssemblerResult::Error { marker: None, message, body: None, diagnostics: vec![] })
+            }
+        }
     } else {
         AssembledRegions::Split(export_regions(&binary, &options))
     };
@@ -0,0 +1,298 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use titan::assembler::binary::{Binary, RegionFlags};
+use crate::diagnostics::{strip_comment, SourcePosition};
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+pub enum SymbolSection {
+    Text,
+    Data,
+}
+
+// The data directives following a label, up to the next label.
+#[derive(Clone, Serialize)]
+pub struct SymbolData {
+    // Name of the first directive without the dot, like "word" or "asciiz".
+    pub directive: String,
+    pub size: u32,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u32,
+    pub section: SymbolSection,
+    pub global: bool,
+    // Set for project builds, location is relative to this file.
+    pub path: Option<String>,
+    // Missing for labels defined in another file. Labels from a file pulled in with .include
+    // are only in the binary, so they have no location, no data and count as local.
+    pub location: Option<SourcePosition>,
+    pub data: Option<SymbolData>,
+}
+
+pub(crate) struct Definition {
+    location: SourcePosition,
+    section: SymbolSection,
+    data: Option<SymbolData>,
+}
+
+fn parse_integer(text: &str) -> Option<u32> {
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse::<i64>().ok().map(|value| value as u32)
+    }
+}
+
+// Bytes in the string literals of an .ascii or .asciiz line.
+fn string_sizes(operands: &str) -> Vec<u32> {
+    let mut result = vec![];
+    let mut chars = operands.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue
+        }
+
+        let mut size = 0;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => {
+                    chars.next();
+                    size += 1;
+                }
+                c => size += c.len_utf8() as u32,
+            }
+        }
+
+        result.push(size)
+    }
+
+    result
+}
+
+// Items can repeat with value:count, like MARS.
+fn item_count(operands: &str) -> u32 {
+    operands
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.split_once(':')
+                .and_then(|(_, count)| parse_integer(count))
+                .unwrap_or(1)
+        })
+        .sum()
+}
+
+fn directive_size(directive: &str, operands: &str) -> Option<u32> {
+    Some(match directive {
+        "byte" => item_count(operands),
+        "half" => item_count(operands) * 2,
+        "word" | "float" => item_count(operands) * 4,
+        "double" => item_count(operands) * 8,
+        "space" => parse_integer(operands)?,
+        "ascii" => string_sizes(operands).iter().sum(),
+        "asciiz" => string_sizes(operands).iter().map(|size| size + 1).sum(),
+        _ => return None
+    })
+}
+
+fn section_directive(directive: &str) -> Option<SymbolSection> {
+    match directive {
+        "text" | "ktext" => Some(SymbolSection::Text),
+        "data" | "kdata" | "rdata" | "sdata" => Some(SymbolSection::Data),
+        _ => None
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.'
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+// Identifiers on a line with their columns, skipping strings and comments.
+pub(crate) fn identifiers(line: &str) -> Vec<(usize, &str)> {
+    let code = strip_comment(line);
+
+    let mut result = vec![];
+    let mut quoted = false;
+    let mut start: Option<usize> = None;
+    let mut previous = ' ';
+
+    for (index, c) in code.char_indices().chain(std::iter::once((code.len(), ' '))) {
+        if let Some(begin) = start {
+            if !is_identifier(c) {
+                result.push((begin, &code[begin..index]));
+                start = None;
+            }
+        }
+
+        if c == '"' && previous != '\\' {
+            quoted = !quoted
+        } else if !quoted && start.is_none() && is_identifier_start(c)
+            // Registers, macro parameters and character literals like 'a' or '\n'.
+            && !is_identifier(previous) && !matches!(previous, '$' | '%' | '\'' | '\\') {
+            start = Some(index)
+        }
+
+        previous = c;
+    }
+
+    result
+}
+
+// Names listed by .globl or .global in a file, with where they were declared.
+pub(crate) fn global_declarations(text: &str) -> Vec<(String, SourcePosition)> {
+    let mut result = vec![];
+
+    for (line, code) in text.split('\n').enumerate() {
+        let mut words = identifiers(code).into_iter();
+
+        let Some((_, ".globl" | ".global")) = words.next() else { continue };
+
+        for (column, name) in words {
+            result.push((name.to_string(), SourcePosition { line, column }))
+        }
+    }
+
+    result
+}
+
+// Names listed by .globl or .global.
+pub fn global_names(source: &str) -> HashSet<String> {
+    global_declarations(source)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+// Walks the source for label definitions, tracking the current section and the data after each label.
+pub(crate) fn scan_definitions(source: &str) -> HashMap<String, Definition> {
+    let mut result = HashMap::new();
+
+    let mut section = SymbolSection::Text;
+    // Labels waiting on data, cleared by an instruction or another section.
+    let mut pending: Vec<String> = vec![];
+
+    for (line, text) in source.split('\n').enumerate() {
+        let mut code = strip_comment(text);
+        let mut offset = 0;
+
+        // A line can define several labels before its directive or instruction.
+        while let Some(index) = code.find(':').filter(|index| !code[..*index].contains('"')) {
+            let name = code[..index].trim();
+
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                break
+            }
+
+            let column = offset + code.find(name).unwrap_or(0);
+
+            // Labels stacked on one location share its data, a label after data starts a new run.
+            let has_data = |label: &String| {
+                result.get(label).map(|definition: &Definition| definition.data.is_some()).unwrap_or(false)
+            };
+
+            if pending.iter().any(has_data) {
+                pending.clear()
+            }
+
+            result.insert(name.to_string(), Definition {
+                location: SourcePosition { line, column },
+                section,
+                data: None,
+            });
+
+            pending.push(name.to_string());
+
+            offset += index + 1;
+            code = &code[index + 1..];
+        }
+
+        let code = code.trim();
+
+        if code.is_empty() {
+            continue
+        }
+
+        let (word, operands) = code
+            .split_once(char::is_whitespace)
+            .unwrap_or((code, ""));
+
+        let Some(directive) = word.strip_prefix('.') else {
+            // An instruction ends any data run.
+            pending.clear();
+            continue
+        };
+
+        if let Some(next) = section_directive(directive) {
+            section = next;
+            pending.clear();
+            continue
+        }
+
+        let Some(size) = directive_size(directive, operands) else { continue };
+
+        for label in &pending {
+            if let Some(definition) = result.get_mut(label) {
+                let data = definition.data.get_or_insert_with(|| SymbolData {
+                    directive: directive.to_string(),
+                    size: 0,
+                });
+
+                data.size += size;
+            }
+        }
+    }
+
+    result
+}
+
+fn region_section(binary: &Binary, address: u32) -> SymbolSection {
+    let region = binary.regions.iter().find(|region| {
+        region.address <= address && address - region.address <= region.data.len() as u32
+    });
+
+    match region {
+        Some(region) if !region.flags.contains(RegionFlags::EXECUTABLE) => SymbolSection::Data,
+        Some(_) => SymbolSection::Text,
+        None => SymbolSection::Data,
+    }
+}
+
+// Every label in the binary, sorted by address.
+pub fn symbol_table(binary: &Binary, source: &str) -> Vec<Symbol> {
+    let definitions = scan_definitions(source);
+    let globals = global_names(source);
+
+    let mut result: Vec<Symbol> = binary.labels
+        .iter()
+        .map(|(name, address)| {
+            let definition = definitions.get(name);
+
+            Symbol {
+                name: name.clone(),
+                address: *address,
+                section: definition
+                    .map(|definition| definition.section)
+                    .unwrap_or_else(|| region_section(binary, *address)),
+                global: globals.contains(name),
+                path: None,
+                location: definition.map(|definition| definition.location),
+                data: definition.and_then(|definition| definition.data.clone()),
+            }
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.address.cmp(&b.address).then_with(|| a.name.cmp(&b.name)));
+
+    result
+}
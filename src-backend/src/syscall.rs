@@ -1,4 +1,10 @@
+use crate::audio::AudioChunk;
 use crate::channels::ByteChannel;
+use crate::display::FlushDisplayBody;
+use crate::exception::{handle_exception, handle_syscall_exception, raise_exception, Coprocessor0, ExceptionCode};
+use crate::mmio::MmioDevices;
+use crate::layout::MemoryLayout;
+use crate::logpoint::Logpoints;
 use crate::channels::ByteChannelConsumption::{ConsumeAndContinue, ConsumeAndStop, IgnoreAndStop};
 use crate::syscall::SyscallResult::{
     Aborted, Completed, Exception, Failure, Terminated, Unimplemented, Unknown,
@@ -20,7 +26,8 @@ use titan::cpu::error::Error::{CpuSyscall, CpuTrap};
 use titan::cpu::state::Registers;
 use titan::cpu::Memory;
 use titan::execution::executor::DebugFrame;
-use titan::execution::executor::ExecutorMode::{Invalid,};
+use titan::execution::executor::ExecutorMode;
+use titan::execution::executor::ExecutorMode::{Breakpoint, Invalid};
 use titan::execution::Executor;
 use titan::execution::trackers::Tracker;
 use futures::{FutureExt, select};
@@ -46,6 +53,15 @@ pub enum SyscallResult {
 
 pub trait ConsoleHandler {
     fn print(&mut self, text: &str, error: bool);
+
+    // Logpoint output, handlers should show it apart from program output.
+    fn log(&mut self, text: &str) {
+        self.print(text, false)
+    }
+}
+
+pub trait AudioHandler {
+    fn play(&mut self, chunk: AudioChunk);
 }
 
 pub trait MidiHandler {
@@ -71,9 +87,11 @@ pub struct SyscallState {
     pub cancel_token: CancelToken,
     pub input_buffer: Arc<ByteChannel>,
     pub sync_wake: Option<oneshot::Sender<()>>,
+    pub logpoints: Logpoints,
     heap_start: u32,
     console: Box<dyn ConsoleHandler + Send + Sync>,
     midi: Box<dyn MidiHandler + Send + Sync>,
+    audio: Box<dyn AudioHandler + Send + Sync>,
     time: Arc<dyn TimeHandler + Send + Sync>,
     generators: HashMap<u32, ChaCha8Rng>,
     next_file: u32,
@@ -84,15 +102,18 @@ impl SyscallState {
     pub fn new(
         console: Box<dyn ConsoleHandler + Send + Sync>,
         midi: Box<dyn MidiHandler + Send + Sync>,
+        audio: Box<dyn AudioHandler + Send + Sync>,
         time: Arc<dyn TimeHandler + Send + Sync>,
     ) -> SyscallState {
         SyscallState {
             cancel_token: CancelToken::None,
             input_buffer: Arc::new(ByteChannel::default()),
             sync_wake: None,
-            heap_start: 0x20000000,
+            logpoints: Logpoints::default(),
+            heap_start: MemoryLayout::default().heap,
             console,
             midi,
+            audio,
             time,
             generators: HashMap::from([(0, ChaCha8Rng::from_entropy())]),
             next_file: 3,
@@ -100,6 +121,15 @@ impl SyscallState {
         }
     }
 
+    // Where sbrk starts handing out memory, the layout's heap.
+    pub fn set_heap_start(&mut self, heap_start: u32) {
+        self.heap_start = heap_start
+    }
+
+    pub fn time_handler(&self) -> Arc<dyn TimeHandler + Send + Sync> {
+        self.time.clone()
+    }
+
     pub fn clear_cancelled(&mut self) {
         self.cancel_token = CancelToken::None
     }
@@ -127,6 +157,9 @@ impl SyscallState {
 
 pub struct SyscallDelegate {
     pub state: Arc<Mutex<SyscallState>>,
+    pub cop0: Arc<Mutex<Coprocessor0>>,
+    pub devices: MmioDevices,
+    pub display: Option<FlushDisplayBody>,
 }
 
 fn reg<Mem: Memory, Track: Tracker<Mem>>(debugger: &Executor<Mem, Track>, index: usize) -> u32 {
@@ -154,9 +187,20 @@ fn midi_request(registers: &Registers) -> MidiRequest {
 
 const PRINT_BUFFER_TIME: Duration = Duration::from_millis(5);
 
+// Instructions run() executes before checking back in.
+const RUN_BATCH_SIZE: usize = 1000;
+
+// Device output, audio and interrupts are checked at least this often, in instructions.
+const DEVICE_UPDATE_INTERVAL: u64 = 1000;
+
 impl SyscallDelegate {
-    pub fn new(state: Arc<Mutex<SyscallState>>) -> SyscallDelegate {
-        SyscallDelegate { state }
+    pub fn new(
+        state: Arc<Mutex<SyscallState>>,
+        cop0: Arc<Mutex<Coprocessor0>>,
+        devices: MmioDevices,
+        display: Option<FlushDisplayBody>,
+    ) -> SyscallDelegate {
+        SyscallDelegate { state, cop0, devices, display }
     }
 
     async fn send_print(&self, text: &str) {
@@ -374,7 +418,7 @@ impl SyscallDelegate {
     async fn alloc_heap<Mem: Memory, Track: Tracker<Mem>>(&self, debugger: &Executor<Mem, Track>) -> SyscallResult {
         let count = a0(debugger);
 
-        // Primitive Heap Alloc, assuming 0x20000000 is safe heap.
+        // Primitive Heap Alloc, the layout's heap is marked writable.
         let mut syscall = self.state.lock().unwrap();
         let pointer = syscall.heap_start;
         syscall.heap_start += count;
@@ -724,6 +768,18 @@ impl SyscallDelegate {
         Unimplemented(44)
     }
 
+    async fn present_display<Mem: Memory, Track: Tracker<Mem>>(&self, debugger: &Executor<Mem, Track>) -> SyscallResult {
+        let Some(display) = &self.display else {
+            return Completed
+        };
+
+        let mut display = display.lock().unwrap();
+
+        debugger.with_memory(|memory| display.present(memory));
+
+        Completed
+    }
+
     async fn wrap_cancel<F: FusedFuture<Output = SyscallResult>>(&self, f: F) -> SyscallResult {
         // This is *really* bad code.
         let Some(receiver) = self.state.lock().unwrap().grab_cancel() else {
@@ -771,6 +827,7 @@ impl SyscallDelegate {
             42 => self.wrap_cancel(self.random_int_ranged(state).fuse()).await,
             43 => self.wrap_cancel(self.random_float(state).fuse()).await,
             44 => self.wrap_cancel(self.random_double(state).fuse()).await,
+            60 => self.wrap_cancel(self.present_display(state).fuse()).await,
             _ => Unknown(code),
         }
     }
@@ -786,39 +843,224 @@ impl SyscallDelegate {
                 let code = debugger.with_state(|s| s.registers.line[V0_REG]);
                 let result = self.dispatch(debugger, code).await;
 
+                let fault = match &result {
+                    Exception(error) => Some(Some(*error)),
+                    Unknown(_) => Some(None),
+                    _ => None
+                };
+
                 match result {
                     Completed => {
                         debugger.syscall_handled();
 
                         (None, Some(result), true)
                     }
+                    // Faults and unknown services go to the kernel handler if one is loaded.
+                    _ if fault.map(|error| self.raise_syscall_fault(debugger, error, code)).unwrap_or(false) => {
+                        debugger.override_mode(ExecutorMode::Running);
+
+                        (None, None, true)
+                    }
                     _ => (Some(frame), Some(result), false),
                 }
             }
+            Invalid(error) => {
+                let mut cop0 = self.cop0.lock().unwrap();
+
+                // mfc0 reads Count.
+                cop0.sync(self.devices.registry.now());
+
+                let handled = handle_exception(debugger, &mut cop0, error);
+
+                if handled {
+                    debugger.override_mode(ExecutorMode::Running);
+
+                    (None, None, true)
+                } else {
+                    (Some(frame), None, false)
+                }
+            }
             _ => (Some(frame), None, false),
         }
     }
 
+    fn raise_syscall_fault<Mem: Memory, Track: Tracker<Mem>>(
+        &self, debugger: &Executor<Mem, Track>, error: Option<Error>, code: u32
+    ) -> bool {
+        // read_string and read_file write to memory, the rest only read from it.
+        let store = matches!(code, 8 | 14);
+
+        let mut cop0 = self.cop0.lock().unwrap();
+
+        cop0.sync(self.devices.registry.now());
+
+        handle_syscall_exception(debugger, &mut cop0, error, store)
+    }
+
+    // Prints the logpoint message if the frame stopped on one.
+    // Returns true if execution should continue past the logpoint.
+    fn handle_logpoint<Mem: Memory, Track: Tracker<Mem>>(
+        &self,
+        debugger: &Executor<Mem, Track>,
+        frame: &DebugFrame,
+    ) -> bool {
+        if frame.mode != Breakpoint {
+            return false
+        }
+
+        let mut syscall = self.state.lock().unwrap();
+
+        let message = {
+            let Some(template) = syscall.logpoints.get(frame.registers.pc) else {
+                return false
+            };
+
+            debugger.with_state(|state| template.format(state))
+        };
+
+        syscall.console.log(&format!("{}\n", message));
+
+        debugger.override_mode(ExecutorMode::Running);
+
+        true
+    }
+
+    // Jumps to the kernel handler if an enabled interrupt is pending.
+    fn deliver_interrupts<Mem: Memory, Track: Tracker<Mem>>(&self, debugger: &Executor<Mem, Track>) {
+        let registry = &self.devices.registry;
+
+        registry.sync();
+
+        let lines = registry.lines();
+        let pending = registry.interrupts();
+
+        let mut cop0 = self.cop0.lock().unwrap();
+
+        cop0.sync(registry.now());
+        cop0.set_interrupt(lines & !pending, false);
+        cop0.set_interrupt(pending, true);
+
+        if cop0.interrupt_ready() {
+            // Nothing has run at pc yet, so the handler returns straight to it.
+            let pc = debugger.with_state(|state| state.registers.pc);
+
+            if raise_exception(debugger, &mut cop0, ExceptionCode::Interrupt, pc, None) {
+                registry.acknowledge(pending & cop0.status)
+            }
+        }
+    }
+
+    // Instruction count devices should be updated at next, when the next of them fires
+    // or after DEVICE_UPDATE_INTERVAL instructions.
+    fn next_update(&self) -> u64 {
+        let registry = &self.devices.registry;
+
+        let next = [registry.next_event(), self.cop0.lock().unwrap().next_event()]
+            .into_iter()
+            .flatten()
+            .map(|cycles| cycles as u64)
+            .fold(DEVICE_UPDATE_INTERVAL, u64::min);
+
+        registry.now() + next.max(1)
+    }
+
+    // Catches device clocks and Count up to the instruction count, prints anything written
+    // to the display transmitter and plays the audio samples that are due.
+    fn update_devices<Mem: Memory, Track: Tracker<Mem>>(&self, debugger: &Executor<Mem, Track>) {
+        let registry = &self.devices.registry;
+
+        registry.sync();
+
+        self.cop0.lock().unwrap().sync(registry.now());
+
+        let mut syscall = self.state.lock().unwrap();
+
+        let now = syscall.time.time();
+        let output = debugger.with_memory(|memory| registry.update(memory, now));
+
+        if !output.console.is_empty() {
+            syscall.console.print(&output.console, false);
+        }
+
+        for chunk in output.audio {
+            syscall.audio.play(chunk)
+        }
+    }
+
+    // Runs up to batch instructions. They run in chunks that end at the next device update,
+    // so device clocks advance by the instructions that actually ran and interrupts land between
+    // instructions. Chunks only shrink to single steps when an update or interrupt is due.
     // A syscall will interrupt a batch!
     pub async fn run_batch<Mem: Memory, Track: Tracker<Mem>>(
-        &self, debugger: &Executor<Mem, Track>, batch: usize, should_skip_first: bool, allow_interrupt: bool
+        &self, debugger: &Executor<Mem, Track>, batch: usize, mut should_skip_first: bool, allow_interrupt: bool
     ) -> Option<(DebugFrame, Option<SyscallResult>)> {
-        if !debugger.run_batched(batch, should_skip_first, allow_interrupt).interrupted {
-            return None // no interruption, batch completed successfully
-        }
+        let registry = &self.devices.registry;
 
-        let frame_in = debugger.frame();
+        let mut update_at = registry.now();
+        let mut remaining = batch as u64;
 
-        let (frame, result, recovered) = self.handle_frame(debugger, frame_in).await;
+        while remaining > 0 {
+            if registry.now() >= update_at {
+                self.update_devices(debugger);
+                self.deliver_interrupts(debugger);
 
-        if let Some(frame) = frame {
-            return Some((frame, result));
-        }
+                update_at = self.next_update();
+            } else if registry.take_changed() {
+                // A device was written to, it may have raised a line or started counting down.
+                self.deliver_interrupts(debugger);
+
+                update_at = update_at.min(self.next_update());
+            }
+
+            let count = update_at.saturating_sub(registry.now()).clamp(1, remaining);
+
+            let result = debugger.run_batched(count as usize, should_skip_first, allow_interrupt);
+            let executed = result.instructions_executed as u64;
+
+            should_skip_first = false;
 
-        if !recovered {
-            return Some((debugger.frame(), None))
+            registry.advance(executed);
+            remaining = remaining.saturating_sub(executed);
+
+            if !result.interrupted {
+                continue
+            }
+
+            let frame = debugger.frame();
+
+            // Syscalls and faults ran their instruction, breakpoints and pauses stop before it.
+            if matches!(frame.mode, Invalid(_)) {
+                registry.advance(1);
+                remaining = remaining.saturating_sub(1);
+            }
+
+            if self.handle_logpoint(debugger, &frame) {
+                // Step over the logpoint.
+                should_skip_first = true;
+
+                continue
+            }
+
+            let (frame, result, recovered) = self.handle_frame(debugger, frame).await;
+
+            if let Some(frame) = frame {
+                self.update_devices(debugger);
+
+                return Some((frame, result));
+            }
+
+            if !recovered {
+                self.update_devices(debugger);
+
+                return Some((debugger.frame(), None))
+            }
+
+            // mtc0 or eret may have unmasked an interrupt.
+            update_at = registry.now();
         }
 
+        self.update_devices(debugger);
+
         None
     }
 
@@ -826,18 +1068,15 @@ impl SyscallDelegate {
         &self, debugger: &Executor<Mem, Track>, mut should_skip_first: bool
     ) -> (DebugFrame, Option<SyscallResult>) {
         loop {
-            let frame = debugger.run(should_skip_first);
-            let (frame, result, recovered) = self.handle_frame(debugger, frame).await;
+            let result = self.run_batch(
+                debugger, RUN_BATCH_SIZE, should_skip_first, true
+            ).await;
 
-            should_skip_first = false;
-
-            if let Some(frame) = frame {
-                return (frame, result);
+            if let Some(result) = result {
+                return result
             }
 
-            if !recovered {
-                return (debugger.frame(), None);
-            }
+            should_skip_first = false;
         }
     }
 }
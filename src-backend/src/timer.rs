@@ -0,0 +1,141 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use titan::cpu::error;
+use titan::cpu::error::Error::MemoryUnmapped;
+use crate::exception::INTERRUPT_CLOCK;
+use crate::mmio::{set_word_byte, word_byte, MmioDevice};
+use crate::syscall::TimeHandler;
+
+// Cheap timing without going through syscall 30.
+//   0xFFFF0020 instructions executed, low word
+//   0xFFFF0024 instructions executed, high word (latched when the low word is read)
+//   0xFFFF0028 milliseconds since the program started
+//   0xFFFF002C compare, in milliseconds (writing re-arms the interrupt)
+//   0xFFFF0030 bit 0 enables the compare interrupt
+pub const TIMER_ADDRESS: u32 = 0xFFFF0020;
+pub const TIMER_END: u32 = 0xFFFF0034;
+const TIMER_RANGE: Range<u32> = TIMER_ADDRESS..TIMER_END;
+
+pub const TIMER_INTERRUPT_ENABLE: u32 = 0b1;
+
+#[derive(Default)]
+pub struct TimerState {
+    cycles: u64,
+    latched: u32,
+    clock: Option<Arc<dyn TimeHandler + Send + Sync>>,
+    start: Option<Duration>,
+    compare: u32,
+    control: u32,
+    // Raised at most once for each write to compare.
+    armed: bool,
+    pending: bool,
+}
+
+#[derive(Default)]
+pub struct TimerHandler {
+    pub state: Arc<Mutex<TimerState>>,
+}
+
+impl TimerState {
+    // The millisecond clock reads zero until a clock is attached.
+    pub fn set_clock(&mut self, clock: Arc<dyn TimeHandler + Send + Sync>) {
+        self.start = clock.time();
+        self.clock = Some(clock);
+    }
+
+    pub fn millis(&self) -> u32 {
+        let now = self.clock.as_ref().and_then(|clock| clock.time());
+
+        match (self.start, now) {
+            (Some(start), Some(now)) => now.saturating_sub(start).as_millis() as u32,
+            _ => 0
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        self.cycles = self.cycles.wrapping_add(cycles as u64);
+
+        if self.armed && self.control & TIMER_INTERRUPT_ENABLE != 0
+            && self.millis().wrapping_sub(self.compare) as i32 >= 0 {
+            self.armed = false;
+            self.pending = true;
+        }
+    }
+}
+
+impl MmioDevice for TimerHandler {
+    fn name(&self) -> &'static str {
+        "timer"
+    }
+
+    fn ranges(&self) -> &[Range<u32>] {
+        std::slice::from_ref(&TIMER_RANGE)
+    }
+
+    fn read(&self, address: u32) -> error::Result<u8> {
+        let offset = address.wrapping_sub(TIMER_ADDRESS);
+
+        let mut state = self.state.lock().unwrap();
+
+        let value = match offset / 4 {
+            // Reading the low word latches the high word, so the two halves agree.
+            0 => {
+                if offset % 4 == 0 {
+                    state.latched = (state.cycles >> 32) as u32
+                }
+
+                state.cycles as u32
+            }
+            1 => state.latched,
+            2 => state.millis(),
+            3 => state.compare,
+            4 => state.control,
+            _ => return Err(MemoryUnmapped(address))
+        };
+
+        Ok(word_byte(value, offset))
+    }
+
+    fn write(&self, address: u32, value: u8) -> error::Result<()> {
+        let offset = address.wrapping_sub(TIMER_ADDRESS);
+
+        let mut state = self.state.lock().unwrap();
+
+        match offset / 4 {
+            3 => {
+                set_word_byte(&mut state.compare, offset, value);
+                state.armed = true;
+                state.pending = false;
+            }
+            4 => set_word_byte(&mut state.control, offset, value),
+            // Counters are read only.
+            0..=2 => {}
+            _ => return Err(MemoryUnmapped(address))
+        }
+
+        Ok(())
+    }
+
+    fn tick(&self, cycles: u32) {
+        self.state.lock().unwrap().tick(cycles)
+    }
+
+    fn lines(&self) -> u32 {
+        INTERRUPT_CLOCK
+    }
+
+    fn interrupts(&self) -> u32 {
+        if self.state.lock().unwrap().pending { INTERRUPT_CLOCK } else { 0 }
+    }
+
+    fn acknowledge(&self, lines: u32) {
+        if lines & INTERRUPT_CLOCK != 0 {
+            self.state.lock().unwrap().pending = false
+        }
+    }
+
+    fn set_clock(&self, clock: Arc<dyn TimeHandler + Send + Sync>) {
+        self.state.lock().unwrap().set_clock(clock)
+    }
+}
@@ -1,6 +1,7 @@
 use std::io::Cursor;
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, MutexGuard};
 use tauri::{Manager, Wry};
+use titan::assembler::binary::Binary;
 use titan::cpu::Memory;
 use titan::cpu::memory::section::{ListenResponder, SectionMemory};
 use titan::cpu::memory::watched::WatchedMemory;
@@ -9,12 +10,20 @@ use titan::execution::Executor;
 use titan::execution::trackers::empty::EmptyTracker;
 use titan::execution::trackers::history::HistoryTracker;
 use titan::execution::trackers::Tracker;
-use saturn_backend::build::{assemble_text, AssemblerResult, configure_keyboard, create_elf_state, DisassembleResult, get_binary_finished_pcs, get_elf_finished_pcs, PrintPayload, TIME_TRAVEL_HISTORY_SIZE};
-use saturn_backend::device::{ExecutionState, setup_state, state_from_binary};
+use saturn_backend::build::{assemble_layout, AssemblerResult, configure_keyboard, create_elf_state, DisassembleResult, elf_result, get_binary_finished_pcs, get_elf_finished_pcs, setup_elf_entry, PrintPayload, TIME_TRAVEL_HISTORY_SIZE};
+use saturn_backend::device::{ExecutionState, mount_display, mount_listeners, setup_state, state_from_binary, watch_state};
+use saturn_backend::display::FlushDisplayBody;
+use saturn_backend::elf_debug::read_debug_info;
 use saturn_backend::execution::RewindableDevice;
-use saturn_backend::keyboard::KeyboardState;
+use saturn_backend::layout::{MemoryLayout, MemoryLayoutBody};
+use saturn_backend::manifest::DEFAULT_STACK_SIZE;
+use saturn_backend::mmio::{DeviceConfigBody, MmioDevices};
+use saturn_backend::project::{ProjectFile, ProjectResult};
+use saturn_backend::raw_image::{binary_elf, BinaryFormat};
 use saturn_backend::regions::{AssembledRegions, AssembleRegionsOptions};
-use saturn_backend::syscall::{ConsoleHandler, MidiHandler, SyscallState, TimeHandler};
+use saturn_backend::audio::AudioChunk;
+use saturn_backend::syscall::{AudioHandler, ConsoleHandler, SyscallState};
+use crate::manifest::ManifestBody;
 use crate::midi::ForwardMidi;
 use crate::state::DebuggerBody;
 use crate::time::TokioTimeHandler;
@@ -26,11 +35,27 @@ struct ForwardPrinter {
 impl ConsoleHandler for ForwardPrinter {
     fn print(&mut self, text: &str, error: bool) {
         self.app
-            .emit_all("print", PrintPayload { text, error })
+            .emit_all("print", PrintPayload { text, error, log: false })
+            .ok();
+    }
+
+    fn log(&mut self, text: &str) {
+        self.app
+            .emit_all("print", PrintPayload { text, error: false, log: true })
             .ok();
     }
 }
 
+struct ForwardAudio {
+    app: tauri::AppHandle<Wry>,
+}
+
+impl AudioHandler for ForwardAudio {
+    fn play(&mut self, chunk: AudioChunk) {
+        self.app.emit_all("play-audio", chunk).ok();
+    }
+}
+
 fn forward_print(app: tauri::AppHandle<Wry>) -> Box<dyn ConsoleHandler + Send + Sync> {
     Box::new(ForwardPrinter { app })
 }
@@ -39,178 +64,255 @@ pub fn swap<Listen: ListenResponder + Send + 'static, Track: Tracker<SectionMemo
     mut pointer: MutexGuard<Option<Arc<dyn RewindableDevice>>>,
     debugger: Executor<SectionMemory<Listen>, Track>,
     finished_pcs: Vec<u32>,
-    keyboard: Arc<Mutex<KeyboardState>>,
-    console: Box<dyn ConsoleHandler + Send + Sync>,
-    midi: Box<dyn MidiHandler + Send + Sync>,
-    time: Arc<dyn TimeHandler + Send + Sync>,
+    devices: MmioDevices,
+    delegate: SyscallState,
+    layout: &MemoryLayout,
 ) {
     if let Some(state) = pointer.as_ref() {
         state.pause();
     }
 
-    let wrapped = Arc::new(debugger);
-    let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time)));
-
     // Drop should cancel the last process and kill the other thread.
-    *pointer = Some(Arc::new(ExecutionState {
-        debugger: wrapped,
-        keyboard,
+    *pointer = Some(Arc::new(ExecutionState::new(
+        debugger,
+        devices,
         delegate,
         finished_pcs,
-    }));
+        layout,
+    )));
 }
 
 pub fn swap_watched<Mem: Memory + Send + 'static>(
     mut pointer: MutexGuard<Option<Arc<dyn RewindableDevice>>>,
     debugger: Executor<WatchedMemory<Mem>, HistoryTracker>,
     finished_pcs: Vec<u32>,
-    keyboard: Arc<Mutex<KeyboardState>>,
-    console: Box<dyn ConsoleHandler + Send + Sync>,
-    midi: Box<dyn MidiHandler + Send + Sync>,
-    time: Arc<dyn TimeHandler + Send + Sync>,
+    devices: MmioDevices,
+    delegate: SyscallState,
+    layout: &MemoryLayout,
 ) {
     if let Some(state) = pointer.as_ref() {
         state.pause();
     }
 
-    let wrapped = Arc::new(debugger);
-    let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time)));
-
     // Drop should cancel the last process and kill the other thread.
-    *pointer = Some(Arc::new(ExecutionState {
-        debugger: wrapped,
-        keyboard,
+    *pointer = Some(Arc::new(ExecutionState::new(
+        debugger,
+        devices,
         delegate,
         finished_pcs,
-    }));
+        layout,
+    )));
 }
 
 #[tauri::command]
 pub fn configure_elf(
     bytes: Vec<u8>,
     time_travel: bool,
+    format: Option<BinaryFormat>,
     state: tauri::State<'_, DebuggerBody>,
+    display: tauri::State<'_, FlushDisplayBody>,
+    config: tauri::State<'_, DeviceConfigBody>,
+    layout: tauri::State<'_, MemoryLayoutBody>,
     app_handle: tauri::AppHandle<Wry>,
-) -> bool {
-    let Ok(elf) = Elf::read(&mut Cursor::new(bytes)) else { return false };
+) -> Option<AssemblerResult> {
+    let Ok(bytes) = binary_elf(bytes, &format.unwrap_or_default()) else { return None };
+    let Ok(elf) = Elf::read(&mut Cursor::new(&bytes)) else { return None };
+
+    let layout = *layout.lock().unwrap();
 
+    let info = read_debug_info(&bytes);
     let finished_pcs = get_elf_finished_pcs(&elf);
     
     let console = forward_print(app_handle.clone());
-    let midi = Box::new(ForwardMidi::new(app_handle));
+    let midi = Box::new(ForwardMidi::new(app_handle.clone()));
+    let audio = Box::new(ForwardAudio { app: app_handle });
     let time = Arc::new(TokioTimeHandler::new());
+    let delegate = SyscallState::new(console, midi, audio, time);
     let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
 
     let mut memory = SectionMemory::new();
-    let keyboard = configure_keyboard(&mut memory);
+    let devices = configure_keyboard(&mut memory, &config.lock().unwrap(), &layout);
+    mount_display(&mut memory, &display.lock().unwrap(), &layout);
 
-    if time_travel {
-        let memory = WatchedMemory::new(memory);
-
-        let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-        setup_state(&mut cpu_state);
+    let mut cpu_state = create_elf_state(&elf, DEFAULT_STACK_SIZE, &layout, memory);
+    mount_listeners(&mut cpu_state.memory, &devices, &mut display.lock().unwrap(), &layout);
+    setup_state(&mut cpu_state, &layout);
+    setup_elf_entry(&mut cpu_state, &elf, info.as_ref(), &finished_pcs);
 
+    if time_travel {
         swap_watched(
             state.lock().unwrap(),
-            Executor::new(cpu_state, history),
+            Executor::new(watch_state(cpu_state), history),
             finished_pcs,
-            keyboard,
-            console,
-            midi,
-            time,
+            devices,
+            delegate,
+            &layout,
         );
     } else {
-        let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-        setup_state(&mut cpu_state);
-
         swap(
             state.lock().unwrap(),
             Executor::new(cpu_state, EmptyTracker { }),
             finished_pcs,
-            keyboard,
-            console,
-            midi,
-            time,
+            devices,
+            delegate,
+            &layout,
         );
     }
 
-    true
+    Some(elf_result(info))
 }
 
-#[tauri::command]
-pub fn configure_asm(
-    text: &str,
-    path: Option<&str>,
+// Loads an assembled binary into a fresh execution state, set up by the project manifest if one is loaded.
+fn run_binary(
+    binary: Binary,
     time_travel: bool,
+    layout: MemoryLayout,
     state: tauri::State<'_, DebuggerBody>,
     app_handle: tauri::AppHandle<Wry>,
-) -> AssemblerResult {
-    let binary = assemble_text(text, path);
+) -> Result<(), String> {
+    let display = app_handle.state::<FlushDisplayBody>();
+    let config = app_handle.state::<DeviceConfigBody>();
 
-    let (binary, result) = AssemblerResult::from_result_with_binary(binary, text);
+    let (manifest, input) = {
+        let loaded = app_handle.state::<ManifestBody>();
+        let loaded = loaded.lock().unwrap();
 
-    let Some(binary) = binary else { return result };
+        let manifest = loaded.as_ref().map(|loaded| loaded.manifest.clone()).unwrap_or_default();
+        let input = loaded.as_ref().and_then(|loaded| loaded.read_input());
+
+        (manifest, input)
+    };
+
+    let time_travel = manifest.time_travel(time_travel);
+    let stack_size = manifest.stack_size();
+    let entry = manifest.entry_address(&binary)?;
 
     let finished_pcs = get_binary_finished_pcs(&binary);
 
     let console = forward_print(app_handle.clone());
-    let midi = Box::new(ForwardMidi::new(app_handle));
+    let midi = Box::new(ForwardMidi::new(app_handle.clone()));
+    let audio = Box::new(ForwardAudio { app: app_handle.clone() });
     let time = Arc::new(TokioTimeHandler::new());
+    let delegate = SyscallState::new(console, midi, audio, time);
     let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
 
     let mut memory = SectionMemory::new();
-    let keyboard = configure_keyboard(&mut memory);
+    let devices = configure_keyboard(&mut memory, &config.lock().unwrap(), &layout);
+    mount_display(&mut memory, &display.lock().unwrap(), &layout);
 
-    if time_travel {
-        let memory = WatchedMemory::new(memory);
-
-        let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-        setup_state(&mut cpu_state);
+    let mut cpu_state = state_from_binary(binary, stack_size, &layout, memory);
+    mount_listeners(&mut cpu_state.memory, &devices, &mut display.lock().unwrap(), &layout);
+    setup_state(&mut cpu_state, &layout);
+    manifest.setup(&mut cpu_state, entry)?;
 
+    if time_travel {
         swap_watched(
             state.lock().unwrap(),
-            Executor::new(cpu_state, history),
+            Executor::new(watch_state(cpu_state), history),
             finished_pcs,
-            keyboard,
-            console,
-            midi,
-            time,
+            devices,
+            delegate,
+            &layout,
         );
     } else {
-        let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-        setup_state(&mut cpu_state);
-
         swap(
             state.lock().unwrap(),
             Executor::new(cpu_state, EmptyTracker { }),
             finished_pcs,
-            keyboard,
-            console,
-            midi,
-            time,
+            devices,
+            delegate,
+            &layout,
         );
     }
 
+    if let (Some(input), Some(device)) = (input, state.lock().unwrap().as_ref()) {
+        device.post_input(input)
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn configure_project(
+    files: Vec<ProjectFile>,
+    time_travel: bool,
+    state: tauri::State<'_, DebuggerBody>,
+    app_handle: tauri::AppHandle<Wry>,
+) -> Result<ProjectResult, String> {
+    let layout = *app_handle.state::<MemoryLayoutBody>().lock().unwrap();
+
+    let (binary, result) = saturn_backend::project::assemble_project(&files, &layout);
+
+    if let Some(binary) = binary {
+        run_binary(binary, time_travel, layout, state, app_handle)?;
+    }
+
+    Ok(result)
+}
+
+// Builds the files listed by the loaded manifest.
+#[tauri::command]
+pub fn configure_manifest(
+    time_travel: bool,
+    state: tauri::State<'_, DebuggerBody>,
+    manifest: tauri::State<'_, ManifestBody>,
+    app_handle: tauri::AppHandle<Wry>,
+) -> Result<ProjectResult, String> {
+    let files = match manifest.lock().unwrap().as_ref() {
+        Some(loaded) => loaded.read_files()?,
+        None => return Err("No project manifest is loaded".to_string())
+    };
+
+    configure_project(files, time_travel, state, app_handle)
+}
+
+#[tauri::command]
+pub fn configure_asm(
+    text: &str,
+    path: Option<&str>,
+    time_travel: bool,
+    state: tauri::State<'_, DebuggerBody>,
+    app_handle: tauri::AppHandle<Wry>,
+) -> AssemblerResult {
+    let layout = *app_handle.state::<MemoryLayoutBody>().lock().unwrap();
+
+    let (binary, result) = assemble_layout(text, path, &layout);
+
+    let Some(binary) = binary else { return result };
+
+    if let Err(message) = run_binary(binary, time_travel, layout, state, app_handle) {
+        return AssemblerResult::Error { marker: None, message, body: None, diagnostics: vec![] }
+    }
+
     result
 }
 
 #[tauri::command]
-pub fn assemble(text: &str, path: Option<&str>) -> AssemblerResult {
-    saturn_backend::build::assemble(text, path)
+pub fn assemble(text: &str, path: Option<&str>, layout: tauri::State<'_, MemoryLayoutBody>) -> AssemblerResult {
+    assemble_layout(text, path, &layout.lock().unwrap()).1
+}
+
+#[tauri::command]
+pub fn assemble_binary(
+    text: &str, path: Option<&str>, include_source: bool, layout: tauri::State<'_, MemoryLayoutBody>
+) -> (Option<Vec<u8>>, AssemblerResult) {
+    saturn_backend::build::assemble_binary(text, path, &layout.lock().unwrap(), include_source)
 }
 
 #[tauri::command]
-pub fn assemble_binary(text: &str, path: Option<&str>) -> (Option<Vec<u8>>, AssemblerResult) {
-    saturn_backend::build::assemble_binary(text, path)
+pub fn assemble_regions(
+    text: &str, path: Option<&str>, options: AssembleRegionsOptions, layout: tauri::State<'_, MemoryLayoutBody>
+) -> (Option<AssembledRegions>, AssemblerResult) {
+    saturn_backend::regions::assemble_regions(text, path, options, &layout.lock().unwrap())
 }
 
 #[tauri::command]
-pub fn assemble_regions(text: &str, path: Option<&str>, options: AssembleRegionsOptions) -> (Option<AssembledRegions>, AssemblerResult) {
-    saturn_backend::regions::assemble_regions(text, path, options)
+pub fn assemble_project(files: Vec<ProjectFile>, layout: tauri::State<'_, MemoryLayoutBody>) -> ProjectResult {
+    saturn_backend::project::assemble_project(&files, &layout.lock().unwrap()).1
 }
 
 #[tauri::command]
-pub fn disassemble(named: Option<&str>, bytes: Vec<u8>) -> DisassembleResult {
-    saturn_backend::build::disassemble(named, bytes)
+pub fn disassemble(named: Option<&str>, bytes: Vec<u8>, format: Option<BinaryFormat>) -> DisassembleResult {
+    saturn_backend::build::disassemble(named, bytes, &format.unwrap_or_default())
 }
 
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::state::DebuggerBody;
 
 #[tauri::command]
@@ -10,6 +10,13 @@ pub fn swap_breakpoints(breakpoints: Vec<u32>, state: tauri::State<'_, DebuggerB
     pointer.set_breakpoints(breakpoints_set)
 }
 
+#[tauri::command]
+pub fn set_logpoints(logpoints: HashMap<u32, String>, state: tauri::State<'_, DebuggerBody>) -> Result<(), String> {
+    let Some(pointer) = &*state.lock().unwrap() else { return Ok(()) };
+
+    pointer.set_logpoints(logpoints)
+}
+
 #[tauri::command]
 pub fn read_bytes(
     address: u32,
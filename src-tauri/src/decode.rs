@@ -1,4 +1,5 @@
 use saturn_backend::decode::{InspectionItem, InstructionDetails};
+use saturn_backend::raw_image::BinaryFormat;
 
 #[tauri::command]
 pub fn decode_instruction(pc: u32, instruction: u32) -> Option<InstructionDetails> {
@@ -6,6 +7,6 @@ pub fn decode_instruction(pc: u32, instruction: u32) -> Option<InstructionDetail
 }
 
 #[tauri::command]
-pub fn detailed_disassemble(bytes: Vec<u8>) -> Result<Vec<InspectionItem>, String> {
-    saturn_backend::decode::detailed_disassemble(bytes)
+pub fn detailed_disassemble(bytes: Vec<u8>, format: Option<BinaryFormat>) -> Result<Vec<InspectionItem>, String> {
+    saturn_backend::decode::detailed_disassemble(bytes, &format.unwrap_or_default())
 }
@@ -1,25 +1,133 @@
 use std::error::Error;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use serde::Deserialize;
 use tauri::{AppHandle, Manager, Wry};
 use tauri::http::{Request, Response, ResponseBuilder};
 use tauri::http::method::Method;
-use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::display::{DisplayFrame, DisplayOptions, DisplayRecorder, FlushDisplayBody, FlushDisplayState, RecordingSummary, DEFAULT_FRAME_DELAY};
+use saturn_backend::layout::MemoryLayoutBody;
+use crate::access_manager::{AccessFilter, AccessManager};
 use crate::state::DebuggerBody;
+use crate::time::TokioTimeHandler;
+
+// The last stopped recording, kept around until it's saved or replaced.
+pub type DisplayRecordingBody = Mutex<Option<DisplayRecorder>>;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    Gif,
+    Png, // numbered files in a directory
+}
 
 #[tauri::command]
-pub fn configure_display(address: u32, width: u32, height: u32, state: tauri::State<FlushDisplayBody>) {
+pub fn configure_display(
+    address: u32,
+    width: u32,
+    height: u32,
+    options: Option<DisplayOptions>,
+    state: tauri::State<FlushDisplayBody>,
+    layout: tauri::State<MemoryLayoutBody>,
+    recording: tauri::State<DisplayRecordingBody>,
+) -> Option<RecordingSummary> {
+    let layout = *layout.lock().unwrap();
     let mut body = state.lock().unwrap();
 
-    *body = FlushDisplayState {
-        address,
-        width,
-        height,
-        data: None,
-    }
+    let mut display = FlushDisplayState::new(address, width, height, options.unwrap_or_default(), &layout);
+    let stopped = display.take_recording(&mut body);
+
+    *body = display;
+
+    keep_stopped(stopped, &recording)
+}
+
+// A recording that couldn't follow the display to its new size is kept so it can still be saved.
+// Returns its summary so the front end knows it stopped.
+pub fn keep_stopped(stopped: Option<DisplayRecorder>, recording: &DisplayRecordingBody) -> Option<RecordingSummary> {
+    let stopped = stopped?;
+    let summary = stopped.summary();
+
+    *recording.lock().unwrap() = Some(stopped);
+
+    Some(summary)
 }
 
 #[tauri::command]
-pub fn last_display(state: tauri::State<FlushDisplayBody>) -> FlushDisplayState {
-    state.lock().unwrap().clone()
+pub fn last_display(state: tauri::State<FlushDisplayBody>) -> DisplayFrame {
+    state.lock().unwrap().last_frame()
+}
+
+// frame_delay is only used if the clock is unavailable.
+#[tauri::command]
+pub fn start_recording(frame_delay: Option<u32>, state: tauri::State<FlushDisplayBody>) {
+    let clock = Arc::new(TokioTimeHandler::new());
+
+    state.lock().unwrap().start_recording(frame_delay.unwrap_or(DEFAULT_FRAME_DELAY), clock)
+}
+
+// Returns the number of distinct frames captured, and whether the size limit cut the recording short.
+#[tauri::command]
+pub fn stop_recording(
+    state: tauri::State<FlushDisplayBody>,
+    recording: tauri::State<DisplayRecordingBody>
+) -> RecordingSummary {
+    let recorder = state.lock().unwrap().stop_recording();
+    let summary = recorder.as_ref()
+        .map(|recorder| recorder.summary())
+        .unwrap_or(RecordingSummary { frames: 0, truncated: false });
+
+    *recording.lock().unwrap() = recorder;
+
+    summary
+}
+
+#[tauri::command]
+pub async fn save_recording(
+    format: RecordingFormat,
+    recording: tauri::State<'_, DisplayRecordingBody>,
+    access: tauri::State<'_, AccessManager>
+) -> Result<String, String> {
+    match format {
+        RecordingFormat::Gif => {
+            let data = {
+                let recording = recording.lock().unwrap();
+                let recorder = recording.as_ref().ok_or("No recording to save")?;
+
+                recorder.encode_gif()?
+            };
+
+            let filters = [AccessFilter { name: "GIF".into(), extensions: vec!["gif".into()] }];
+
+            let destination = access.select_save("Save Recording", &filters, false).await
+                .ok_or("Save cancelled")?;
+
+            fs::write(&destination, data).map_err(|error| error.to_string())?;
+
+            Ok(destination.to_string_lossy().to_string())
+        }
+        RecordingFormat::Png => {
+            let frames = {
+                let recording = recording.lock().unwrap();
+                let recorder = recording.as_ref().ok_or("No recording to save")?;
+
+                recorder.encode_pngs()?
+            };
+
+            let destination = access.select_save("Save Frames Directory", &[], false).await
+                .ok_or("Save cancelled")?;
+
+            fs::create_dir_all(&destination).map_err(|error| error.to_string())?;
+
+            for (index, frame) in frames.into_iter().enumerate() {
+                let path = destination.join(format!("frame_{:05}.png", index));
+
+                fs::write(path, frame).map_err(|error| error.to_string())?;
+            }
+
+            Ok(destination.to_string_lossy().to_string())
+        }
+    }
 }
 
 pub fn display_protocol(
@@ -29,7 +137,8 @@ pub fn display_protocol(
     // Disable CORS, nothing super private here.
     let builder = ResponseBuilder::new()
         .header("Access-Control-Allow-Headers", "*")
-        .header("Access-Control-Allow-Origin", "*");
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Expose-Headers", "delta");
 
     // Check for preflight, very primitive check.
     if request.method() == Method::OPTIONS {
@@ -54,14 +163,43 @@ pub fn display_protocol(
         return builder.status(400).body(vec![])
     };
 
+    // With a delta header, only rows that changed since the last read are sent,
+    // in the span format of FlushDisplayState::take_delta.
+    // Deltas and unit scaling only apply to the configured display, other reads get the raw memory.
+    let delta = request.headers().get("delta").is_some();
+
     let state: tauri::State<'_, DebuggerBody> = app.state();
+    let pointer = state.lock().unwrap().clone();
 
-    let Some(pointer) = &*state.lock().unwrap() else {
-        return builder.status(400).body(vec![])
-    };
+    let display: tauri::State<'_, FlushDisplayBody> = app.state();
+    let mut display = display.lock().unwrap();
+
+    if !display.matches(address, width, height) {
+        let format = display.format;
+
+        drop(display);
+
+        let Some(pointer) = pointer else {
+            return builder.status(400).body(vec![])
+        };
+
+        let Some(result) = pointer.read_display(address, width, height, format) else {
+            return builder.status(400).body(vec![])
+        };
 
+        return builder.body(result)
+    }
+
+    // Double buffered displays only change on present.
+    if let Some(pointer) = &pointer {
+        pointer.flush_display(&mut display)
+    }
+
+    if delta {
+        return builder.header("delta", "1").body(display.take_delta())
+    }
 
-    let Some(result) = pointer.read_display(address, width, height) else {
+    let Some(result) = display.take_frame() else {
         return builder.status(400).body(vec![])
     };
 
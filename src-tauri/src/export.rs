@@ -35,7 +35,7 @@ pub async fn export_hex_regions(regions: Vec<HexRegion>, state: tauri::State<'_,
     fs::create_dir_all(&destination).ok();
 
     for region in regions {
-        let path = destination.join(format!("{}.txt", region.name));
+        let path = destination.join(format!("{}.{}", region.name, region.extension));
 
         write_region_contents(&path, &region.data);
     }
@@ -14,6 +14,7 @@ mod watch;
 mod export;
 mod decode;
 mod display;
+mod manifest;
 mod time;
 
 use std::sync::{Arc, Mutex};
@@ -22,20 +23,23 @@ use tauri::WindowEvent::{Destroyed, FileDrop};
 use crate::access_manager::AccessManager;
 
 use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::mmio::{DeviceConfig, DeviceConfigBody};
+use saturn_backend::layout::{MemoryLayout, MemoryLayoutBody};
 use crate::menu::{create_menu, handle_event};
 
-use crate::build::{assemble, assemble_binary, assemble_regions, configure_asm, configure_elf, disassemble};
-use crate::debug::{read_bytes, set_register, swap_breakpoints, write_bytes};
+use crate::build::{assemble, assemble_binary, assemble_project, assemble_regions, configure_asm, configure_elf, configure_manifest, configure_project, disassemble};
+use crate::manifest::{clear_manifest, load_manifest, ManifestBody};
+use crate::debug::{read_bytes, set_logpoints, set_register, swap_breakpoints, write_bytes};
 use crate::menu::platform_shortcuts;
 use crate::midi::{midi_install, midi_protocol, MidiProviderContainer};
 use crate::export::{export_binary_contents, export_hex_contents, export_hex_regions};
 use crate::state::DebuggerBody;
 
-use crate::state::{last_pc, pause, post_input, post_key, resume, rewind, stop, wake_sync};
+use crate::state::{configure_devices, configure_memory, device_input, device_snapshots, last_pc, pause, post_input, post_key, post_mouse, resume, rewind, stop, wake_sync};
 use crate::testing::{all_tests, run_tests};
 
 use crate::decode::{decode_instruction, detailed_disassemble};
-use crate::display::{configure_display, last_display, display_protocol};
+use crate::display::{configure_display, last_display, display_protocol, save_recording, start_recording, stop_recording, DisplayRecordingBody};
 
 #[tauri::command]
 fn is_debug() -> bool {
@@ -48,6 +52,10 @@ fn main() {
     tauri::Builder::default()
         .manage(Mutex::new(None) as DebuggerBody)
         .manage(Arc::new(Mutex::new(FlushDisplayState::default())) as FlushDisplayBody)
+        .manage(Mutex::new(None) as DisplayRecordingBody)
+        .manage(Arc::new(Mutex::new(DeviceConfig::default())) as DeviceConfigBody)
+        .manage(Arc::new(Mutex::new(MemoryLayout::default())) as MemoryLayoutBody)
+        .manage(Mutex::new(None) as ManifestBody)
         .manage(Mutex::new(MidiProviderContainer::None))
         .menu(menu)
         .setup(|app| {
@@ -79,8 +87,13 @@ fn main() {
             disassemble,        // build
             assemble_binary,    // build
             assemble_regions,   // build
+            assemble_project,   // build
             configure_elf,      // build
             configure_asm,      // build
+            configure_project,  // build
+            configure_manifest, // build
+            load_manifest,      // manifest
+            clear_manifest,     // manifest
             resume,             // execution
             rewind,             // execution
             pause,              // execution
@@ -90,10 +103,19 @@ fn main() {
             write_bytes,        // debug
             set_register,       // debug
             swap_breakpoints,   // debug
+            set_logpoints,      // debug
             post_key,           // bitmap
+            post_mouse,         // bitmap
             post_input,         // bitmap
+            device_input,       // mmio devices
+            configure_devices,  // devices
+            configure_memory,   // memory
+            device_snapshots,   // devices
             configure_display,  // bitmap
             last_display,       // bitmap
+            start_recording,    // bitmap
+            stop_recording,     // bitmap
+            save_recording,     // bitmap
             access_manager::access_sync,
             access_manager::access_select_save,
             access_manager::access_select_open,
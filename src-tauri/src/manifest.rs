@@ -0,0 +1,150 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::layout::{MemoryLayout, MemoryLayoutBody};
+use saturn_backend::manifest::{ProjectManifest, MANIFEST_NAME};
+use saturn_backend::mmio::{DeviceConfig, DeviceConfigBody};
+use saturn_backend::project::ProjectFile;
+use crate::access_manager::{AccessError, AccessManager};
+use crate::display::{keep_stopped, DisplayRecordingBody};
+
+pub struct LoadedManifest {
+    // Paths in the manifest are relative to this.
+    pub directory: PathBuf,
+    pub manifest: ProjectManifest,
+    // The manifest's files and input, resolved inside directory.
+    pub files: Vec<PathBuf>,
+    pub input: Option<PathBuf>,
+}
+
+// What the front end needs to build the project itself.
+#[derive(Serialize)]
+pub struct LoadedProject {
+    pub manifest: ProjectManifest,
+    pub files: Vec<PathBuf>,
+}
+
+impl LoadedManifest {
+    pub fn read_files(&self) -> Result<Vec<ProjectFile>, String> {
+        self.files
+            .iter()
+            .map(|path| {
+                let text = fs::read_to_string(path)
+                    .map_err(|_| AccessError::NotFound(path.clone()).to_string())?;
+
+                Ok(ProjectFile { path: path.to_string_lossy().to_string(), text })
+            })
+            .collect()
+    }
+
+    pub fn read_input(&self) -> Option<String> {
+        fs::read_to_string(self.input.as_ref()?).ok()
+    }
+}
+
+// Manifest paths are relative and can't leave the project directory, even through a link.
+fn resolve(directory: &Path, file: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(file);
+
+    if relative.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("{} has to be a path inside the project directory", file))
+    }
+
+    let path = directory.join(relative);
+
+    let resolved = path.canonicalize()
+        .map_err(|_| AccessError::NotFound(path.clone()).to_string())?;
+
+    if !resolved.starts_with(directory) {
+        return Err(format!("{} has to be a path inside the project directory", file))
+    }
+
+    Ok(resolved)
+}
+
+pub type ManifestBody = Mutex<Option<LoadedManifest>>;
+
+// Takes the manifest itself or the project directory holding it.
+// Display, device and memory settings are applied right away, the rest on the next build.
+#[tauri::command]
+pub fn load_manifest(
+    path: PathBuf,
+    state: tauri::State<'_, ManifestBody>,
+    display: tauri::State<'_, FlushDisplayBody>,
+    config: tauri::State<'_, DeviceConfigBody>,
+    layout: tauri::State<'_, MemoryLayoutBody>,
+    access: tauri::State<'_, AccessManager>,
+    recording: tauri::State<'_, DisplayRecordingBody>,
+) -> Result<LoadedProject, String> {
+    let path = if path.is_dir() { path.join(MANIFEST_NAME) } else { path };
+
+    if !access.has_access(&path) {
+        return Err(AccessError::AccessDenied(path).to_string())
+    }
+
+    let text = fs::read_to_string(&path)
+        .map_err(|_| AccessError::NotFound(path.clone()).to_string())?;
+
+    let manifest: ProjectManifest = toml::from_str(&text)
+        .map_err(|error| format!("Could not read {}: {}", path.to_string_lossy(), error))?;
+
+    let directory = path.canonicalize()
+        .ok()
+        .and_then(|path| path.parent().map(|parent| parent.to_path_buf()))
+        .ok_or_else(|| AccessError::NotFound(path.clone()).to_string())?;
+
+    let files = manifest.files
+        .iter()
+        .map(|file| resolve(&directory, file))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let input = manifest.run.input
+        .as_ref()
+        .map(|file| resolve(&directory, file))
+        .transpose()?;
+
+    // The manifest vouches for the project's own files.
+    access.permit(files.iter().chain(input.iter()).cloned().collect());
+
+    let memory = manifest.layout();
+
+    if let Some(settings) = &manifest.display {
+        // Bases follow the project's memory layout.
+        let mut screen = FlushDisplayState::new(
+            settings.address, settings.width, settings.height, settings.options.clone(), &memory
+        );
+
+        let mut display = display.lock().unwrap();
+        let stopped = screen.take_recording(&mut display);
+
+        *display = screen;
+
+        keep_stopped(stopped, &recording);
+    }
+
+    *layout.lock().unwrap() = memory;
+
+    *config.lock().unwrap() = manifest.devices.clone();
+
+    *state.lock().unwrap() = Some(LoadedManifest {
+        directory, manifest: manifest.clone(), files: files.clone(), input
+    });
+
+    Ok(LoadedProject { manifest, files })
+}
+
+// Memory and device settings from the manifest go back to their defaults.
+#[tauri::command]
+pub fn clear_manifest(
+    state: tauri::State<'_, ManifestBody>,
+    config: tauri::State<'_, DeviceConfigBody>,
+    layout: tauri::State<'_, MemoryLayoutBody>,
+) {
+    *layout.lock().unwrap() = MemoryLayout::default();
+
+    *config.lock().unwrap() = DeviceConfig::default();
+
+    *state.lock().unwrap() = None
+}
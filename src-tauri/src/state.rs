@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use serde_json::Value;
 use titan::execution::executor::ExecutorMode;
 use saturn_backend::display::FlushDisplayBody;
+use saturn_backend::mmio::{DeviceConfig, DeviceConfigBody};
+use saturn_backend::layout::{MemoryLayout, MemoryLayoutBody, MemoryPreset};
 use saturn_backend::execution::{BatchOptions, ResumeOptions, ResumeResult, RewindableDevice};
 
 pub type DebuggerBody = Mutex<Option<Arc<dyn RewindableDevice>>>;
@@ -68,10 +72,45 @@ pub fn stop(state: tauri::State<'_, DebuggerBody>) {
 }
 
 #[tauri::command]
-pub fn post_key(key: char, up: bool, state: tauri::State<'_, DebuggerBody>) {
+pub fn post_key(key: String, up: bool, state: tauri::State<'_, DebuggerBody>) {
     let Some(pointer) = &*state.lock().unwrap() else { return };
 
-    pointer.post_key(key, up)
+    pointer.post_key(&key, up)
+}
+
+#[tauri::command]
+pub fn post_mouse(x: u32, y: u32, buttons: u8, state: tauri::State<'_, DebuggerBody>) {
+    let Some(pointer) = &*state.lock().unwrap() else { return };
+
+    pointer.post_mouse(x, y, buttons)
+}
+
+// Input for the device with this name, like {"key": 3} for the digital lab keypad.
+#[tauri::command]
+pub fn device_input(name: String, input: Value, state: tauri::State<'_, DebuggerBody>) -> Result<(), String> {
+    let Some(pointer) = &*state.lock().unwrap() else { return Ok(()) };
+
+    pointer.device_input(&name, input)
+}
+
+// Device name -> state, for devices that publish one.
+#[tauri::command]
+pub fn device_snapshots(state: tauri::State<'_, DebuggerBody>) -> Option<HashMap<String, Value>> {
+    let Some(pointer) = &*state.lock().unwrap() else { return None };
+
+    Some(pointer.device_snapshots())
+}
+
+// Takes effect the next time a program is configured.
+#[tauri::command]
+pub fn configure_devices(config: DeviceConfig, state: tauri::State<'_, DeviceConfigBody>) {
+    *state.lock().unwrap() = config
+}
+
+// Takes effect the next time a program is assembled or configured.
+#[tauri::command]
+pub fn configure_memory(preset: MemoryPreset, state: tauri::State<'_, MemoryLayoutBody>) {
+    *state.lock().unwrap() = MemoryLayout::preset(preset)
 }
 
 #[tauri::command]
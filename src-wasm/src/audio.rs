@@ -0,0 +1,14 @@
+use std::sync::Arc;
+use saturn_backend::audio::AudioChunk;
+use saturn_backend::syscall::AudioHandler;
+use crate::EventHandler;
+
+pub struct WasmAudio {
+    pub events: Arc<EventHandler>
+}
+
+impl AudioHandler for WasmAudio {
+    fn play(&mut self, chunk: AudioChunk) {
+        self.events.send_audio_play(chunk)
+    }
+}
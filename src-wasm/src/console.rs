@@ -8,6 +8,10 @@ pub struct WasmConsole {
 
 impl ConsoleHandler for WasmConsole {
     fn print(&mut self, text: &str, error: bool) {
-        self.events.send_console_write(text, error)
+        self.events.send_console_write(text, error, false)
+    }
+
+    fn log(&mut self, text: &str) {
+        self.events.send_console_write(text, false, true)
     }
 }
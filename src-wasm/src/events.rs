@@ -1,12 +1,14 @@
 use send_wrapper::SendWrapper;
 use wasm_bindgen::JsValue;
 use wasm_bindgen::prelude::wasm_bindgen;
+use saturn_backend::audio::AudioChunk;
 use saturn_backend::midi::note::MidiNote;
 
 #[wasm_bindgen]
 pub struct EventHandler {
     on_console_write: SendWrapper<js_sys::Function>,
     on_midi_play: SendWrapper<js_sys::Function>,
+    on_audio_play: Option<SendWrapper<js_sys::Function>>,
 }
 
 #[wasm_bindgen]
@@ -15,20 +17,23 @@ impl EventHandler {
     pub fn new(
         on_console_write: js_sys::Function,
         on_midi_play: js_sys::Function,
+        on_audio_play: Option<js_sys::Function>,
     ) -> EventHandler {
         EventHandler {
             on_console_write: SendWrapper::new(on_console_write),
-            on_midi_play: SendWrapper::new(on_midi_play)
+            on_midi_play: SendWrapper::new(on_midi_play),
+            on_audio_play: on_audio_play.map(SendWrapper::new)
         }
     }
 }
 
 impl EventHandler {
-    pub fn send_console_write(&self, text: &str, error: bool) {
-        self.on_console_write.call2(
+    pub fn send_console_write(&self, text: &str, error: bool, log: bool) {
+        self.on_console_write.call3(
             &JsValue::UNDEFINED,
             &JsValue::from_str(text),
-            &JsValue::from_bool(error)
+            &JsValue::from_bool(error),
+            &JsValue::from_bool(log)
         ).ok();
     }
 
@@ -38,4 +43,14 @@ impl EventHandler {
             &serde_wasm_bindgen::to_value(&note).unwrap()
         ).ok();
     }
+
+    // Audio is dropped if no callback was given.
+    pub fn send_audio_play(&self, chunk: AudioChunk) {
+        let Some(on_audio_play) = &self.on_audio_play else { return };
+
+        on_audio_play.call1(
+            &JsValue::UNDEFINED,
+            &serde_wasm_bindgen::to_value(&chunk).unwrap()
+        ).ok();
+    }
 }
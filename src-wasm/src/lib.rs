@@ -1,14 +1,15 @@
+mod audio;
 mod console;
 mod midi;
 mod time;
 mod events;
 
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
-use titan::assembler::string::assemble_from;
+use titan::assembler::binary::Binary;
 use titan::cpu::Memory;
 use titan::cpu::memory::section::{ListenResponder, SectionMemory};
 use titan::cpu::memory::watched::WatchedMemory;
@@ -19,12 +20,18 @@ use titan::execution::trackers::empty::EmptyTracker;
 use titan::execution::trackers::history::HistoryTracker;
 use titan::execution::trackers::Tracker;
 use wasm_bindgen::prelude::*;
-use saturn_backend::build::{AssemblerResult, configure_keyboard, create_elf_state, get_binary_finished_pcs, get_elf_finished_pcs, TIME_TRAVEL_HISTORY_SIZE};
-use saturn_backend::device::{ExecutionState, setup_state, state_from_binary};
-use saturn_backend::display::{FlushDisplayBody, FlushDisplayState};
+use saturn_backend::build::{assemble_layout, configure_keyboard, create_elf_state, elf_result, get_binary_finished_pcs, get_elf_finished_pcs, setup_elf_entry, TIME_TRAVEL_HISTORY_SIZE};
+use saturn_backend::device::{ExecutionState, mount_display, mount_listeners, setup_state, state_from_binary, watch_state};
+use saturn_backend::display::{DisplayOptions, FlushDisplayBody, FlushDisplayState};
+use saturn_backend::elf_debug::read_debug_info;
 use saturn_backend::execution::{BatchOptions, ResumeOptions, RewindableDevice};
-use saturn_backend::keyboard::KeyboardState;
-use saturn_backend::syscall::{ConsoleHandler, MidiHandler, SyscallState, TimeHandler};
+use saturn_backend::layout::{MemoryLayout, MemoryPreset};
+use saturn_backend::manifest::DEFAULT_STACK_SIZE;
+use saturn_backend::mmio::{DeviceConfig, MmioDevices};
+use saturn_backend::project::ProjectFile;
+use saturn_backend::raw_image::{binary_elf, BinaryFormat};
+use saturn_backend::syscall::SyscallState;
+use crate::audio::WasmAudio;
 use crate::console::WasmConsole;
 use crate::midi::WasmMidi;
 use crate::time::WasmTime;
@@ -38,28 +45,6 @@ pub fn initialize() {
     console_error_panic_hook::set_once();
 }
 
-#[wasm_bindgen]
-pub fn assemble_regions(text: &str, options: JsValue) -> JsValue {
-    let result = saturn_backend::regions::assemble_regions(
-        text, None, serde_wasm_bindgen::from_value(options).unwrap());
-
-    serde_wasm_bindgen::to_value(&result).unwrap()
-}
-
-#[wasm_bindgen]
-pub fn assemble_text(text: &str) -> JsValue {
-    let result = saturn_backend::build::assemble(text, None);
-
-    serde_wasm_bindgen::to_value(&result).unwrap()
-}
-
-#[wasm_bindgen]
-pub fn assemble_binary(text: &str) -> JsValue {
-    let result = saturn_backend::build::assemble_binary(text, None);
-
-    serde_wasm_bindgen::to_value(&result).unwrap()
-}
-
 #[wasm_bindgen]
 pub fn decode_instruction(pc: u32, instruction: u32) -> JsValue {
     let result = saturn_backend::decode::decode_instruction(pc, instruction);
@@ -68,15 +53,17 @@ pub fn decode_instruction(pc: u32, instruction: u32) -> JsValue {
 }
 
 #[wasm_bindgen]
-pub fn disassemble(named: Option<String>, bytes: Vec<u8>) -> JsValue {
-    let result = saturn_backend::build::disassemble(named.as_deref(), bytes);
+pub fn disassemble(named: Option<String>, bytes: Vec<u8>, format: JsValue) -> JsValue {
+    let format: BinaryFormat = serde_wasm_bindgen::from_value(format).unwrap_or_default();
+    let result = saturn_backend::build::disassemble(named.as_deref(), bytes, &format);
 
     serde_wasm_bindgen::to_value(&result).unwrap()
 }
 
 #[wasm_bindgen]
-pub fn detailed_disassemble(bytes: Vec<u8>) -> Result<JsValue, String> {
-    let result = saturn_backend::decode::detailed_disassemble(bytes)?;
+pub fn detailed_disassemble(bytes: Vec<u8>, format: JsValue) -> Result<JsValue, String> {
+    let format: BinaryFormat = serde_wasm_bindgen::from_value(format).unwrap_or_default();
+    let result = saturn_backend::decode::detailed_disassemble(bytes, &format)?;
 
     Ok(serde_wasm_bindgen::to_value(&result).unwrap())
 }
@@ -85,6 +72,8 @@ pub fn detailed_disassemble(bytes: Vec<u8>) -> Result<JsValue, String> {
 pub struct Runner {
     events: Arc<EventHandler>,
     display: RefCell<FlushDisplayBody>,
+    devices: RefCell<DeviceConfig>,
+    layout: RefCell<MemoryLayout>,
     device: RefCell<Option<Rc<dyn RewindableDevice>>>
 }
 
@@ -93,52 +82,81 @@ impl Runner {
         self.device.borrow().clone()
     }
     
+    // Loads an assembled binary into a fresh execution state.
+    fn run_binary(&self, binary: Binary, time_travel: bool) {
+        let finished_pcs = get_binary_finished_pcs(&binary);
+
+        let console = Box::new(WasmConsole { events: self.events.clone() });
+        let midi = Box::new(WasmMidi { });
+        let audio = Box::new(WasmAudio { events: self.events.clone() });
+        let time = Arc::new(WasmTime { });
+        let delegate = SyscallState::new(console, midi, audio, time);
+        let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+        let layout = *self.layout.borrow();
+
+        let mut memory = SectionMemory::new();
+        let devices = configure_keyboard(&mut memory, &self.devices.borrow(), &layout);
+        mount_display(&mut memory, &self.display.borrow().lock().unwrap(), &layout);
+
+        let mut cpu_state = state_from_binary(binary, DEFAULT_STACK_SIZE, &layout, memory);
+        mount_listeners(&mut cpu_state.memory, &devices, &mut self.display.borrow().lock().unwrap(), &layout);
+        setup_state(&mut cpu_state, &layout);
+
+        if time_travel {
+            self.swap_watched(
+                Executor::new(watch_state(cpu_state), history),
+                finished_pcs,
+                devices,
+                delegate,
+            );
+        } else {
+            self.swap(
+                Executor::new(cpu_state, EmptyTracker { }),
+                finished_pcs,
+                devices,
+                delegate,
+            );
+        }
+    }
+
     pub fn swap<Listen: ListenResponder + Send + 'static, Track: Tracker<SectionMemory<Listen>> + Send + 'static>(
         &self,
         debugger: Executor<SectionMemory<Listen>, Track>,
         finished_pcs: Vec<u32>,
-        keyboard: Arc<Mutex<KeyboardState>>,
-        console: Box<dyn ConsoleHandler + Send + Sync>,
-        midi: Box<dyn MidiHandler + Send + Sync>,
-        time: Arc<dyn TimeHandler + Send + Sync>,
+        devices: MmioDevices,
+        delegate: SyscallState,
     ) {
         if let Some(device) = &self.take_device() {
             device.pause()
         }
 
-        let wrapped = Arc::new(debugger);
-        let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time)));
-
-        *self.device.borrow_mut() = Some(Rc::new(ExecutionState {
-            debugger: wrapped,
-            keyboard,
+        *self.device.borrow_mut() = Some(Rc::new(ExecutionState::new(
+            debugger,
+            devices,
             delegate,
             finished_pcs,
-        }));
+            &self.layout.borrow(),
+        )));
     }
 
     pub fn swap_watched<Mem: Memory + Send + 'static>(
         &self,
         debugger: Executor<WatchedMemory<Mem>, HistoryTracker>,
         finished_pcs: Vec<u32>,
-        keyboard: Arc<Mutex<KeyboardState>>,
-        console: Box<dyn ConsoleHandler + Send + Sync>,
-        midi: Box<dyn MidiHandler + Send + Sync>,
-        time: Arc<dyn TimeHandler + Send + Sync>,
+        devices: MmioDevices,
+        delegate: SyscallState,
     ) {
         if let Some(device) = &self.take_device() {
             device.pause()
         }
 
-        let wrapped = Arc::new(debugger);
-        let delegate = Arc::new(Mutex::new(SyscallState::new(console, midi, time)));
-
-        *self.device.borrow_mut() = Some(Rc::new(ExecutionState {
-            debugger: wrapped,
-            keyboard,
+        *self.device.borrow_mut() = Some(Rc::new(ExecutionState::new(
+            debugger,
+            devices,
             delegate,
             finished_pcs,
-        }));
+            &self.layout.borrow(),
+        )));
     }
 }
 
@@ -149,6 +167,8 @@ impl Runner {
         Runner {
             events: Arc::new(events),
             display: RefCell::new(Arc::new(Mutex::new(Default::default()))),
+            devices: RefCell::new(DeviceConfig::default()),
+            layout: RefCell::new(MemoryLayout::default()),
             device: RefCell::new(None),
         }
     }
@@ -159,68 +179,127 @@ impl Runner {
         }
     }
 
+    pub fn set_logpoints(&self, logpoints: JsValue) -> Result<(), String> {
+        let logpoints: HashMap<u32, String> = serde_wasm_bindgen::from_value(logpoints)
+            .map_err(|error| error.to_string())?;
+
+        if let Some(device) = &self.take_device() {
+            device.set_logpoints(logpoints)?
+        }
+
+        Ok(())
+    }
+
     pub fn last_display(&self) -> JsValue {
         let display_borrow = self.display.borrow();
         let display = display_borrow.lock().unwrap();
 
-        serde_wasm_bindgen::to_value(&*display).unwrap()
+        serde_wasm_bindgen::to_value(&display.last_frame()).unwrap()
+    }
+
+    pub fn configure_display(&self, address: u32, width: u32, height: u32, options: JsValue) {
+        let options: Option<DisplayOptions> = serde_wasm_bindgen::from_value(options).ok();
+        let layout = *self.layout.borrow();
+
+        let mut display = FlushDisplayState::new(address, width, height, options.unwrap_or_default(), &layout);
+        display.take_recording(&mut self.display.borrow().lock().unwrap());
+
+        *self.display.borrow_mut() = Arc::new(Mutex::new(display));
+    }
+
+    pub fn assemble_regions(&self, text: &str, options: JsValue) -> Result<JsValue, String> {
+        let options = serde_wasm_bindgen::from_value(options)
+            .map_err(|error| error.to_string())?;
+
+        let result = saturn_backend::regions::assemble_regions(text, None, options, &self.layout.borrow());
+
+        Ok(serde_wasm_bindgen::to_value(&result).unwrap())
+    }
+
+    pub fn assemble_text(&self, text: &str) -> JsValue {
+        let (_, result) = assemble_layout(text, None, &self.layout.borrow());
+
+        serde_wasm_bindgen::to_value(&result).unwrap()
+    }
+
+    pub fn assemble_project(&self, files: JsValue) -> Result<JsValue, String> {
+        let files: Vec<ProjectFile> = serde_wasm_bindgen::from_value(files)
+            .map_err(|error| error.to_string())?;
+
+        let (_, result) = saturn_backend::project::assemble_project(&files, &self.layout.borrow());
+
+        Ok(serde_wasm_bindgen::to_value(&result).unwrap())
+    }
+
+    pub fn assemble_binary(&self, text: &str, include_source: bool) -> JsValue {
+        let result = saturn_backend::build::assemble_binary(text, None, &self.layout.borrow(), include_source);
+
+        serde_wasm_bindgen::to_value(&result).unwrap()
+    }
+
+    // Takes effect on the next configure_elf or configure_asm.
+    pub fn configure_devices(&self, config: JsValue) {
+        *self.devices.borrow_mut() = serde_wasm_bindgen::from_value(config).unwrap_or_default();
     }
 
-    pub fn configure_display(&self, address: u32, width: u32, height: u32) {
-        *self.display.borrow_mut() = Arc::new(Mutex::new(FlushDisplayState {
-            address,
-            width,
-            height,
-            data: None,
-        }));
+    // Takes a preset name, like "compact_text_at_zero". Also applies to the next configure_* call.
+    pub fn configure_memory(&self, preset: JsValue) -> Result<(), String> {
+        let preset: MemoryPreset = serde_wasm_bindgen::from_value(preset)
+            .map_err(|error| error.to_string())?;
+
+        *self.layout.borrow_mut() = MemoryLayout::preset(preset);
+
+        Ok(())
     }
 
     pub fn configure_elf(
         &self,
         bytes: Vec<u8>,
-        time_travel: bool
-    ) -> bool {
-        let Ok(elf) = Elf::read(&mut Cursor::new(bytes)) else { return false };
+        time_travel: bool,
+        format: JsValue,
+    ) -> JsValue {
+        let format: BinaryFormat = serde_wasm_bindgen::from_value(format).unwrap_or_default();
+
+        let Ok(bytes) = binary_elf(bytes, &format) else { return JsValue::NULL };
+        let Ok(elf) = Elf::read(&mut Cursor::new(&bytes)) else { return JsValue::NULL };
 
+        let info = read_debug_info(&bytes);
         let finished_pcs = get_elf_finished_pcs(&elf);
 
         let console = Box::new(WasmConsole { events: self.events.clone() });
         let midi = Box::new(WasmMidi { });
+        let audio = Box::new(WasmAudio { events: self.events.clone() });
         let time = Arc::new(WasmTime { });
+        let delegate = SyscallState::new(console, midi, audio, time);
         let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
+        let layout = *self.layout.borrow();
 
         let mut memory = SectionMemory::new();
-        let keyboard = configure_keyboard(&mut memory);
+        let devices = configure_keyboard(&mut memory, &self.devices.borrow(), &layout);
+        mount_display(&mut memory, &self.display.borrow().lock().unwrap(), &layout);
 
-        if time_travel {
-            let memory = WatchedMemory::new(memory);
-
-            let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-            setup_state(&mut cpu_state);
+        let mut cpu_state = create_elf_state(&elf, DEFAULT_STACK_SIZE, &layout, memory);
+        mount_listeners(&mut cpu_state.memory, &devices, &mut self.display.borrow().lock().unwrap(), &layout);
+        setup_state(&mut cpu_state, &layout);
+        setup_elf_entry(&mut cpu_state, &elf, info.as_ref(), &finished_pcs);
 
+        if time_travel {
             self.swap_watched(
-                Executor::new(cpu_state, history),
+                Executor::new(watch_state(cpu_state), history),
                 finished_pcs,
-                keyboard,
-                console,
-                midi,
-                time,
+                devices,
+                delegate,
             );
         } else {
-            let mut cpu_state = create_elf_state(&elf, 0x100000, memory);
-            setup_state(&mut cpu_state);
-
             self.swap(
                 Executor::new(cpu_state, EmptyTracker { }),
                 finished_pcs,
-                keyboard,
-                console,
-                midi,
-                time,
+                devices,
+                delegate,
             );
         }
 
-        true
+        serde_wasm_bindgen::to_value(&elf_result(info)).unwrap()
     }
 
     pub fn configure_asm(
@@ -228,53 +307,28 @@ impl Runner {
         text: &str,
         time_travel: bool,
     ) -> JsValue {
-        let binary = assemble_from(text);
-
-        let (binary, result) = AssemblerResult::from_result_with_binary(binary, text);
+        let (binary, result) = assemble_layout(text, None, &self.layout.borrow());
 
         let Some(binary) = binary else {
             return serde_wasm_bindgen::to_value(&result).unwrap()
         };
 
-        let finished_pcs = get_binary_finished_pcs(&binary);
-
-        let console = Box::new(WasmConsole { events: self.events.clone() });
-        let midi = Box::new(WasmMidi { });
-        let time = Arc::new(WasmTime { });
-        let history = HistoryTracker::new(TIME_TRAVEL_HISTORY_SIZE);
-
-        let mut memory = SectionMemory::new();
-        let keyboard = configure_keyboard(&mut memory);
+        self.run_binary(binary, time_travel);
 
-        if time_travel {
-            let memory = WatchedMemory::new(memory);
+        serde_wasm_bindgen::to_value(&result).unwrap()
+    }
 
-            let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-            setup_state(&mut cpu_state);
+    pub fn configure_project(&self, files: JsValue, time_travel: bool) -> Result<JsValue, String> {
+        let files: Vec<ProjectFile> = serde_wasm_bindgen::from_value(files)
+            .map_err(|error| error.to_string())?;
 
-            self.swap_watched(
-                Executor::new(cpu_state, history),
-                finished_pcs,
-                keyboard,
-                console,
-                midi,
-                time,
-            );
-        } else {
-            let mut cpu_state = state_from_binary(binary, 0x100000, memory);
-            setup_state(&mut cpu_state);
+        let (binary, result) = saturn_backend::project::assemble_project(&files, &self.layout.borrow());
 
-            self.swap(
-                Executor::new(cpu_state, EmptyTracker { }),
-                finished_pcs,
-                keyboard,
-                console,
-                midi,
-                time,
-            );
+        if let Some(binary) = binary {
+            self.run_binary(binary, time_travel);
         }
 
-        serde_wasm_bindgen::to_value(&result).unwrap()
+        Ok(serde_wasm_bindgen::to_value(&result).unwrap())
     }
     
     pub fn last_pc(&self) -> Option<u32> {
@@ -308,10 +362,34 @@ impl Runner {
         }
     }
 
-    pub fn post_key(&self, key: char, up: bool) {
+    pub fn post_key(&self, key: String, up: bool) {
+        if let Some(device) = &self.take_device() {
+            device.post_key(&key, up)
+        }
+    }
+
+    pub fn post_mouse(&self, x: u32, y: u32, buttons: u8) {
+        if let Some(device) = &self.take_device() {
+            device.post_mouse(x, y, buttons)
+        }
+    }
+
+    // Input for the device with this name, like {"key": 3} for the digital lab keypad.
+    pub fn device_input(&self, name: String, input: JsValue) -> Result<(), String> {
+        let input: serde_json::Value = serde_wasm_bindgen::from_value(input)
+            .map_err(|error| error.to_string())?;
+
         if let Some(device) = &self.take_device() {
-            device.post_key(key, up)
+            device.device_input(&name, input)?
         }
+
+        Ok(())
+    }
+
+    pub fn device_snapshots(&self) -> JsValue {
+        let result = self.take_device().map(|device| device.device_snapshots());
+
+        serde_wasm_bindgen::to_value(&result).unwrap()
     }
 
     pub fn wake_sync(&self) {
@@ -320,12 +398,41 @@ impl Runner {
         }
     }
 
+    // Frames for the configured display are scaled to pixels, other reads return the memory as is.
     pub fn read_display(&self, address: u32, width: u32, height: u32) -> Option<Vec<u8>> {
+        let display = self.display.borrow().clone();
+        let mut display = display.lock().unwrap();
+
+        if !display.matches(address, width, height) {
+            let format = display.format;
+
+            drop(display);
+
+            return self.take_device()?.read_display(address, width, height, format)
+        }
+
         if let Some(device) = &self.take_device() {
-            device.read_display(address, width, height)
-        } else {
-            None
+            device.flush_display(&mut display)
+        }
+
+        display.take_frame()
+    }
+
+    // Rows of the configured display changed since the last read, see FlushDisplayState::take_delta for the format.
+    // None for any other display, read_display has to be used instead.
+    pub fn take_display_delta(&self, address: u32, width: u32, height: u32) -> Option<Vec<u8>> {
+        let display = self.display.borrow().clone();
+        let mut display = display.lock().unwrap();
+
+        if !display.matches(address, width, height) {
+            return None
         }
+
+        if let Some(device) = &self.take_device() {
+            device.flush_display(&mut display)
+        }
+
+        Some(display.take_delta())
     }
 
     pub async fn resume(&self, batch_size: usize, breakpoints: Option<Vec<u32>>, first_batch: bool, is_step: bool) -> JsValue {
@@ -16,10 +16,10 @@ export const backend = createBackend()
 
 export function setupBackend(): Promise<void> {
   return backend.setCallbacks({
-    consoleWrite(text: string, error: boolean) {
+    consoleWrite(text: string, error: boolean, log: boolean) {
       pushConsole(
         text,
-        error ? ConsoleType.Stderr : ConsoleType.Stdout
+        log ? ConsoleType.Log : error ? ConsoleType.Stderr : ConsoleType.Stdout
       )
     },
 
@@ -19,12 +19,14 @@ export enum ConsoleType {
   Secondary,
   Editing,
   Submitted,
+  Log, // logpoint output
 }
 
 function canConcat(value: ConsoleType): boolean {
   switch (value) {
     case ConsoleType.Stdout:
     case ConsoleType.Stderr:
+    case ConsoleType.Log:
       return true
 
     default:
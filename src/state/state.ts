@@ -11,6 +11,7 @@ import { GotoMessage, useGoto } from '../utils/goto'
 import { useSymbolHighlight } from '../utils/symbol-highlight'
 import { ref, watch } from 'vue'
 import { InstructionLine } from '../utils/mips/mips'
+import { LoadedProject } from '../utils/query/project-manifest'
 
 export const settings = useSettings()
 
@@ -86,6 +87,9 @@ function showSuggestionsAt(cursor: SelectionIndex) {
 
 export const showExportRegionsDialog = ref(false)
 
+// Line whose logpoint is being edited.
+export const editingLogpoint = ref(null as number | null)
+
 export const {
   range,
   position,
@@ -110,3 +114,6 @@ export const {
 )
 
 export const buildLines = ref(null as InstructionLine[] | null)
+
+// Set while a saturn.toml project is open, builds and runs then cover all of its files.
+export const project = ref(null as LoadedProject | null)
@@ -9,13 +9,19 @@ import {
 import { backend } from '../state/backend'
 import {
   AssemblerResult,
+  Diagnostic,
   ExecutionModeType,
-  ExecutionResult
+  ExecutionProfile,
+  ExecutionResult,
+  ProjectFile,
+  projectAssemblerResult
 } from './mips/mips'
-import { tab, settings } from '../state/state'
+import { tab, settings, project, tabsState } from '../state/state'
 
 import { format } from 'date-fns'
-import { PromptType, saveCurrentTab } from './events'
+import { PromptType, saveCurrentTab, saveTab } from './events'
+import { LoadedProject } from './query/project-manifest'
+import { accessReadText } from './query/access-manager'
 import { computed, toRaw } from 'vue'
 
 export async function setBreakpoint(line: number, remove: boolean) {
@@ -38,6 +44,38 @@ export async function setBreakpoint(line: number, remove: boolean) {
   }
 }
 
+// An empty message removes the logpoint.
+export async function setLogpoint(line: number, message: string) {
+  const currentTab = tab()
+
+  if (!currentTab) {
+    return
+  }
+
+  const logpoints = { ...(currentTab.logpoints ?? {}) }
+
+  if (message.length) {
+    logpoints[line] = message
+  } else {
+    delete logpoints[line]
+  }
+
+  currentTab.logpoints = logpoints
+
+  if (consoleData.execution) {
+    await sendLogpoints(logpoints)
+  }
+}
+
+async function sendLogpoints(logpoints: Record<number, string>) {
+  try {
+    await consoleData.execution?.setLogpoints(logpoints)
+  } catch (error) {
+    openConsole()
+    pushConsole(`Logpoint not set: ${error}`, ConsoleType.Error)
+  }
+}
+
 async function postDebugInformationWithPcHint(result: ExecutionResult) {
   postDebugInformation(result)
 
@@ -99,6 +137,38 @@ function closeExecution() {
   consoleData.execution = null
 }
 
+function pushDiagnostics(diagnostics: Diagnostic[]) {
+  const currentPath = tab()?.path ?? null
+
+  for (const diagnostic of diagnostics) {
+    const warning = diagnostic.severity === 'Warning'
+    const fixes = diagnostic.fixes.map((fix) => ` ${fix.message}`).join('')
+
+    // Project builds report errors in other files too.
+    const file = diagnostic.path && diagnostic.path !== currentPath
+      ? `${diagnostic.path.split(/[\\/]/).pop()} `
+      : ''
+
+    pushConsole(
+      `${warning ? 'Warning' : 'Error'}: ${diagnostic.message} (${file}line ${diagnostic.start.line + 1})${fixes}`,
+      warning ? ConsoleType.Info : ConsoleType.Error
+    )
+  }
+}
+
+// Saves open project files, then reads every file of the project.
+async function projectFiles(loaded: LoadedProject): Promise<ProjectFile[]> {
+  for (const open of tabsState.tabs) {
+    if (open.path && loaded.files.includes(open.path)) {
+      await saveTab(open, PromptType.NeverPrompt)
+    }
+  }
+
+  return await Promise.all(
+    loaded.files.map(async (path) => ({ path, text: await accessReadText(path) }))
+  )
+}
+
 export function postBuildMessage(result: AssemblerResult): boolean {
   switch (result.status) {
     case 'Error':
@@ -119,6 +189,7 @@ export function postBuildMessage(result: AssemblerResult): boolean {
         `Build failed: ${result.message}${marker}${trailing}`,
         ConsoleType.Error
       )
+      pushDiagnostics(result.diagnostics.slice(1))
 
       return false
 
@@ -133,6 +204,7 @@ export function postBuildMessage(result: AssemblerResult): boolean {
         `Build succeeded at ${format(Date.now(), 'MMMM d, pp')}`,
         ConsoleType.Success
       )
+      pushDiagnostics(result.diagnostics)
 
       return true
   }
@@ -143,6 +215,16 @@ export async function build() {
 
   const current = tab()
 
+  if (project.value) {
+    const result = await backend.assembleProject(await projectFiles(project.value))
+
+    consoleData.showConsole = true
+    consoleData.tab = DebugTab.Console
+    postBuildMessage(projectAssemblerResult(result, current?.path ?? null))
+
+    return
+  }
+
   const {
     result
   } = await backend.assembleWithBinary(collectLines(current?.lines ?? []), current?.path ?? null)
@@ -194,7 +276,12 @@ export async function resume() {
 
     await saveCurrentTab(PromptType.NeverPrompt)
 
-    consoleData.execution = await backend.createExecution(text, path, settings.execution.timeTravel, current.profile)
+    // An open project runs all of its files, breakpoints still come from the current tab.
+    const profile: ExecutionProfile = project.value
+      ? { kind: 'project', files: await projectFiles(project.value) }
+      : current.profile
+
+    consoleData.execution = await backend.createExecution(text, path, settings.execution.timeTravel, profile)
   }
 
   consoleData.showConsole = true
@@ -212,6 +299,8 @@ export async function resume() {
     }
   }
 
+  await sendLogpoints(toRaw(current.logpoints ?? {}))
+
   const result = await consoleData.execution.resume(
     null,
     toRaw(usedBreakpoints)
@@ -14,13 +14,15 @@ import {
 } from './query/access-manager'
 import { consoleData, ConsoleType, pushConsole } from '../state/console-data'
 import { backend } from '../state/backend'
-import { BinaryResult } from './mips/mips'
+import { BinaryResult, binaryFormat } from './mips/mips'
 import {
   closeTab,
   createTab,
   editor,
   find,
   loadElf,
+  project,
+  settings,
   showExportRegionsDialog,
   showSettings,
   suggestions,
@@ -32,6 +34,7 @@ import { watch } from 'vue'
 import { MidiNote, playNote } from './midi'
 import { splitLines } from './split-lines'
 import { exportBinaryContents } from './query/serialize-files'
+import { clearManifest, loadManifest, manifestName } from './query/project-manifest'
 
 export enum PromptType {
   NeverPrompt,
@@ -44,10 +47,32 @@ interface ConsoleEvent {
   message: string
 }
 
+// Opening a saturn.toml makes its files the project that builds and runs.
+async function openProject(path: string) {
+  try {
+    project.value = await loadManifest(path)
+
+    const { manifest, files } = project.value
+
+    pushConsole(
+      `Opened project ${manifest.name ?? path} with ${files.length} file${files.length === 1 ? '' : 's'}`,
+      ConsoleType.Info
+    )
+  } catch (error) {
+    project.value = null
+
+    pushConsole(`Could not open project: ${error}`, ConsoleType.Error)
+  }
+}
+
 export async function openTab(file: AccessFile<string | Uint8Array>) {
   const { name: inName, path, data } = file
   const name = inName ?? 'Untitled'
 
+  if (name === manifestName && window.__TAURI__) {
+    await openProject(path)
+  }
+
   const existing = tabsState.tabs.find((tab) => tab.path === path)
 
   if (existing) {
@@ -63,7 +88,7 @@ export async function openTab(file: AccessFile<string | Uint8Array>) {
       break
 
     default:
-      await loadElf(name, data.buffer)
+      await loadElf(name, data.buffer, binaryFormat(name))
       break
   }
 }
@@ -109,6 +134,18 @@ export async function saveCurrentTab(
 }
 
 export async function setupEvents() {
+  // Closing the project's saturn.toml closes the project.
+  watch(
+    () => tabsState.tabs.some((tab) => tab.title === manifestName),
+    async (open) => {
+      if (!open && project.value) {
+        project.value = null
+
+        await clearManifest()
+      }
+    }
+  )
+
   await listen('new-tab', () => {
     createTab('Untitled', [''])
   })
@@ -192,7 +229,11 @@ export async function setupEvents() {
     if (current.profile && current.profile.kind === 'elf') {
       binary = Uint8Array.from(window.atob(current.profile.elf), c => c.charCodeAt(0))
     } else {
-      result = await backend.assembleWithBinary(collectLines(current.lines), current.path)
+      result = await backend.assembleWithBinary(
+        collectLines(current.lines),
+        current.path,
+        settings.elf.includeSource
+      )
 
       binary = result.binary
     }
@@ -226,8 +267,9 @@ export async function setupEvents() {
     }
 
     const { name, data } = result
+    const named = name ?? 'Untitled'
 
-    await loadElf(name ?? 'Untitled', data.buffer)
+    await loadElf(named, data.buffer, binaryFormat(named))
   })
 
   await listen('toggle-console', () => {
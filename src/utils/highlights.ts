@@ -9,6 +9,7 @@ export interface Highlights<Message = DefaultMessage> {
   offset: number
   size: number
   message: Message
+  warning?: boolean
 }
 
 export interface HighlightsInterface<Message = DefaultMessage> {
@@ -24,12 +25,21 @@ export interface HighlightsInterface<Message = DefaultMessage> {
     tokens: Token[],
     message: UnwrapRef<Message>
   ): void
+  addHighlight(
+    line: number,
+    tokens: Token[],
+    index: number,
+    message: UnwrapRef<Message>,
+    warning?: boolean
+  ): void
   dismissHighlight(): void
   shiftHighlight(line: number, deleted: number, replaced: number): void
 }
 
 export interface HighlightsState<Message = DefaultMessage> {
   highlight: Highlights<UnwrapRef<Message>> | null
+  // Shown alongside highlight, for reporting several diagnostics at once.
+  others: Highlights<UnwrapRef<Message>>[]
 }
 
 export type HighlightsResult<Message = DefaultMessage> =
@@ -42,14 +52,15 @@ export function useHighlights<Message = DefaultMessage>(
 ): HighlightsResult<Message> {
   const state = reactive({
     highlight: null as Highlights<Message> | null,
+    others: [] as Highlights<Message>[],
   })
 
-  function putHighlight(
+  function measureHighlight(
     line: number,
     tokenIndex: number,
     tokens: Token[],
     message: UnwrapRef<Message>
-  ) {
+  ): Highlights<UnwrapRef<Message>> {
     const token = tokens[tokenIndex]
 
     const { leading, trailing } = grabWhitespace(token.text)
@@ -64,7 +75,16 @@ export function useHighlights<Message = DefaultMessage>(
       token.text.substring(leading.length, token.text.length - trailing.length)
     )
 
-    state.highlight = { line, message, offset, size }
+    return { line, message, offset, size }
+  }
+
+  function putHighlight(
+    line: number,
+    tokenIndex: number,
+    tokens: Token[],
+    message: UnwrapRef<Message>
+  ) {
+    state.highlight = measureHighlight(line, tokenIndex, tokens, message)
   }
 
   function setHighlight(
@@ -82,30 +102,60 @@ export function useHighlights<Message = DefaultMessage>(
     putHighlight(line, tokenIndex, tokens, message)
   }
 
+  function addHighlight(
+    line: number,
+    tokens: Token[],
+    index: number,
+    message: UnwrapRef<Message>,
+    warning?: boolean
+  ) {
+    const tokenIndex = findTokenIndex(tokens, index + 1)
+
+    if (tokenIndex === null) {
+      return
+    }
+
+    const highlight = measureHighlight(line, tokenIndex, tokens, message)
+
+    state.others.push({ ...highlight, warning })
+  }
+
+  // Returns false if the highlight's line was deleted.
+  function shiftOne(
+    highlight: Highlights<UnwrapRef<Message>>,
+    line: number,
+    deleted: number,
+    replaced: number
+  ): boolean {
+    if (line <= highlight.line && highlight.line < line + deleted) {
+      return false
+    } else if (replaced !== deleted && highlight.line >= line + deleted) {
+      highlight.line += replaced - deleted
+    }
+
+    return true
+  }
+
   function shiftHighlight(line: number, deleted: number, replaced: number) {
-    if (state.highlight) {
-      if (
-        line <= state.highlight.line &&
-        state.highlight.line < line + deleted
-      ) {
-        state.highlight = null
-      } else if (
-        replaced !== deleted &&
-        state.highlight.line >= line + deleted
-      ) {
-        state.highlight.line += replaced - deleted
-      }
+    if (state.highlight && !shiftOne(state.highlight, line, deleted, replaced)) {
+      state.highlight = null
     }
+
+    state.others = state.others.filter((highlight) =>
+      shiftOne(highlight, line, deleted, replaced)
+    )
   }
 
   function dismissHighlight() {
     state.highlight = null
+    state.others = []
   }
 
   return {
     state,
     setHighlight,
     putHighlight,
+    addHighlight,
     shiftHighlight,
     dismissHighlight,
   }
@@ -5,6 +5,22 @@ export interface ElfExecutionProfile {
   kind: 'elf'
   elf: string // not sure what to do here
   breakpoints: Record<number, number>
+  // label line -> address, for breakpoints on symbols
+  symbolLines?: Record<number, number>
+  // Set for memory images and hex files, elf holds the file as it was opened.
+  format?: BinaryFormat
+}
+
+export function elfBreakpoints(profile: ElfExecutionProfile): Breakpoint[] {
+  const lines = Object.entries(profile.breakpoints).map(
+    ([pc, line]) => ({ line, pcs: [parseInt(pc)] } as Breakpoint)
+  )
+
+  const symbols = Object.entries(profile.symbolLines ?? {}).map(
+    ([line, pc]) => ({ line: parseInt(line), pcs: [pc] } as Breakpoint)
+  )
+
+  return [...lines, ...symbols]
 }
 
 // should only be set after building
@@ -12,12 +28,42 @@ export interface AssemblyExecutionProfile {
   kind: 'asm'
 }
 
-export type ExecutionProfile = ElfExecutionProfile | AssemblyExecutionProfile
+// Every file of a saturn.toml project, built together.
+export interface ProjectExecutionProfile {
+  kind: 'project'
+  files: ProjectFile[]
+}
+
+export type ExecutionProfile = ElfExecutionProfile | AssemblyExecutionProfile | ProjectExecutionProfile
+
+// How bytes passed to disassemble are laid out, ELF when left out.
+export type BinaryFormat =
+  | { kind: 'elf' }
+  | { kind: 'raw', base: number, endian: 'little' | 'big' }
+  | { kind: 'hex', base: number, encoding: 'byte' | 'big32' | 'little32' }
+
+// Picks the format of an opened file from its extension, images load at the default text address.
+export function binaryFormat(named: string): BinaryFormat {
+  const extension = named.split('.').pop()?.toLowerCase()
+
+  switch (extension) {
+    case 'bin':
+      return { kind: 'raw', base: 0x00400000, endian: 'little' }
+
+    case 'hex':
+      return { kind: 'hex', base: 0x00400000, encoding: 'little32' }
+
+    default:
+      return { kind: 'elf' }
+  }
+}
 
 export interface DisassembleResult {
   error: string | null
   lines: string[]
   breakpoints: Record<number, number>
+  symbols: AssemblerSymbol[]
+  symbol_lines: Record<number, number>
 }
 
 export interface LineMarker {
@@ -30,14 +76,56 @@ export interface Breakpoint {
   pcs: number[]
 }
 
+export type DiagnosticSeverity = 'Error' | 'Warning'
+
+export interface SourcePosition {
+  line: number
+  column: number
+}
+
+export interface FixIt {
+  message: string
+  replacement: string
+}
+
+export interface Diagnostic {
+  severity: DiagnosticSeverity
+  message: string
+  path: string | null
+  start: SourcePosition
+  end: SourcePosition
+  fixes: FixIt[]
+}
+
+export type SymbolSection = 'Text' | 'Data'
+
+export interface SymbolData {
+  directive: string
+  size: number
+}
+
+export interface AssemblerSymbol {
+  name: string
+  address: number
+  section: SymbolSection
+  global: boolean
+  path: string | null
+  location: SourcePosition | null
+  data: SymbolData | null
+}
+
 export interface AssemblerSuccess {
   breakpoints: Breakpoint[]
+  symbols: AssemblerSymbol[]
+  diagnostics: Diagnostic[]
 }
 
+// marker, body and message describe the first error, diagnostics has all of them.
 export interface AssemblerError {
   marker: LineMarker | null
   body: string | null
   message: string
+  diagnostics: Diagnostic[]
 }
 
 export type AssemblerResultSuccess = AssemblerSuccess & { status: 'Success' }
@@ -45,15 +133,71 @@ export type AssemblerResultError = AssemblerError & { status: 'Error' }
 
 export type AssemblerResult = AssemblerResultSuccess | AssemblerResultError
 
+export interface ProjectFile {
+  path: string
+  text: string
+}
+
+export type ProjectResult =
+  | {
+      status: 'Success'
+      breakpoints: Record<string, Breakpoint[]>
+      symbols: AssemblerSymbol[]
+      diagnostics: Diagnostic[]
+    }
+  | { status: 'Error'; diagnostics: Diagnostic[] }
+
+// Project results as seen from the file at path, for the single file build messages.
+export function projectAssemblerResult(result: ProjectResult, path: string | null): AssemblerResult {
+  switch (result.status) {
+    case 'Success':
+      return {
+        status: 'Success',
+        breakpoints: (path ? result.breakpoints[path] : null) ?? [],
+        symbols: result.symbols,
+        diagnostics: result.diagnostics,
+      }
+
+    case 'Error': {
+      const first = result.diagnostics[0]
+      const marker = first && first.path === path
+        ? { line: first.start.line, offset: first.start.column }
+        : null
+
+      return {
+        status: 'Error',
+        message: first?.message ?? 'Project failed to build',
+        body: null,
+        marker,
+        diagnostics: result.diagnostics,
+      }
+    }
+  }
+}
+
 export interface BinaryResult {
   binary: Uint8Array | null
   result: AssemblerResult
 }
 
+// Resolved against the memory layout by the backend, overriding address.
+export type DisplayBase = 'global_pointer' | 'static_data' | 'heap' | 'mmio'
+
 export interface BitmapConfig {
+  // In units, each drawn as unitWidth x unitHeight pixels.
   width: number
   height: number
+  unitWidth: number
+  unitHeight: number
   address: number
+  base: DisplayBase | null
+}
+
+export function displayOptions(config: BitmapConfig) {
+  return {
+    unit: [config.unitWidth, config.unitHeight],
+    base: config.base,
+  }
 }
 
 export enum ExecutionModeType {
@@ -98,8 +242,35 @@ export interface ExecutionResult {
   registers: Registers
 }
 
+// Copies delta spans into an RGBA frame. Each span is the start row and row count
+// (u32 little endian), then count full rows of RGBA pixels.
+export function applyDisplayDelta(frame: Uint8ClampedArray, width: number, delta: Uint8Array) {
+  const view = new DataView(delta.buffer, delta.byteOffset, delta.byteLength)
+  const rowBytes = width * 4
+
+  let offset = 0
+
+  while (offset + 8 <= delta.length) {
+    const start = view.getUint32(offset, true)
+    const count = view.getUint32(offset + 4, true)
+
+    offset += 8
+
+    const pixels = delta.subarray(offset, offset + count * rowBytes)
+
+    if (start * rowBytes + pixels.length > frame.length) {
+      return
+    }
+
+    frame.set(pixels, start * rowBytes)
+
+    offset += pixels.length
+  }
+}
+
 export interface LastDisplay {
   address: number
+  // In pixels.
   width: number
   height: number
   data: number[] | null
@@ -128,6 +299,19 @@ export class Breakpoints {
     return lines.flatMap((line) => this.findNextPc(line))
   }
 
+  // line -> message template, to pc -> template.
+  public mapLogpoints(logpoints: Record<number, string>): Map<number, string> {
+    const result = new Map<number, string>()
+
+    for (const [line, message] of Object.entries(logpoints)) {
+      for (const pc of this.findNextPc(parseInt(line))) {
+        result.set(pc, message)
+      }
+    }
+
+    return result
+  }
+
   // pc -> line
   constructor(breakpoints: Breakpoint[]) {
     this.maxLine = Math.max(...breakpoints.map(b => b.line))
@@ -159,6 +343,7 @@ export class Breakpoints {
 export interface HexRegion {
   name: string
   data: string // base64 encoded
+  extension: string
 }
 
 interface AssembledRegionsBinary {
@@ -222,7 +407,8 @@ export type InstructionLine = InstructionLineInstruction
   | InstructionLineLabel
 
 export interface MipsCallbacks {
-  consoleWrite(text: string, error: boolean): void
+  // log is set for logpoint output.
+  consoleWrite(text: string, error: boolean, log: boolean): void
   midiPlay(note: MidiNote): void
 }
 
@@ -231,15 +417,18 @@ export interface MipsBackend {
 
   // Insight
   decodeInstruction(pc: number, instruction: number): Promise<InstructionDetails | null>
-  disassemblyDetails(bytes: ArrayBuffer): Promise<InstructionLine[]>
+  disassemblyDetails(bytes: ArrayBuffer, format?: BinaryFormat): Promise<InstructionLine[]>
 
   disassembleElf(
     named: string,
-    elf: ArrayBuffer
+    elf: ArrayBuffer,
+    format?: BinaryFormat
   ): Promise<DisassembleResult>
 
   assembleText(text: string, path: string | null): Promise<AssemblerResult>
-  assembleWithBinary(text: string, path: string | null): Promise<BinaryResult>
+  assembleProject(files: ProjectFile[]): Promise<ProjectResult>
+  // includeSource embeds the text in the ELF, for files that will be exported.
+  assembleWithBinary(text: string, path: string | null, includeSource?: boolean): Promise<BinaryResult>
 
   assembleRegions(
     text: string,
@@ -279,8 +468,12 @@ export interface MipsExecution {
   pause(): Promise<void>
   stop(): Promise<void>
   setBreakpoints(breakpoints: number[]): Promise<void>
+  // line -> message template, replaces the previous logpoints. Rejects on a bad template.
+  setLogpoints(logpoints: Record<number, string>): Promise<void>
 
   postKey(key: string, up: boolean): Promise<void>
+  // Cursor in display units, buttons is the MouseEvent.buttons mask (left 1, right 2, middle 4).
+  postMouse(x: number, y: number, buttons: number): Promise<void>
   postInput(text: string): Promise<void>
 
   memoryAt(
@@ -292,4 +485,7 @@ export interface MipsExecution {
 
   // Live display, should generally be more performant on tauri.
   readDisplay(width: number, height: number, address: number): Promise<Uint8Array | null>
+  // Rows of the configured display changed since the last read, see applyDisplayDelta.
+  // Null when the display isn't the configured one, readDisplay still works for it.
+  readDisplayDelta(width: number, height: number, address: number): Promise<Uint8Array | null>
 }
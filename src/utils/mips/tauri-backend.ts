@@ -1,12 +1,14 @@
 import {
   AssembledRegions,
   AssemblerResult,
+  BinaryFormat,
   BinaryResult,
-  BitmapConfig, Breakpoint, Breakpoints, DisassembleResult, ExecutionProfile, ExecutionResult,
+  BitmapConfig, Breakpoints, displayOptions, DisassembleResult, elfBreakpoints, ExecutionProfile, ExecutionResult,
   HexBinaryResult,
   InstructionDetails,
   InstructionLine,
-  LastDisplay, MipsBackend, MipsCallbacks, MipsExecution
+  LastDisplay, MipsBackend, MipsCallbacks, MipsExecution,
+  ProjectFile, projectAssemblerResult, ProjectResult
 } from './mips'
 import { ExportRegionsOptions } from '../settings'
 
@@ -38,19 +40,20 @@ export class TauriExecution implements MipsExecution {
           bytes[i] = text.charCodeAt(i)
         }
 
-        const result = await tauri.invoke('configure_elf', {
+        const result = (await tauri.invoke('configure_elf', {
           bytes,
-          timeTravel: this.timeTravel
-        })
-
-        return result
-          ? { status: 'Success', breakpoints: [] }
-          : {
-            status: 'Error',
-            message: 'Configured ELF was not valid',
-            body: null,
-            marker: null,
-          }
+          timeTravel: this.timeTravel,
+          format: this.profile.format ?? null
+        })) as AssemblerResult | null
+
+        // Breakpoints and symbols are filled in for ELF files exported by Saturn.
+        return result ?? {
+          status: 'Error',
+          message: 'Configured ELF was not valid',
+          body: null,
+          marker: null,
+          diagnostics: [],
+        }
       }
 
       case 'asm': {
@@ -67,6 +70,21 @@ export class TauriExecution implements MipsExecution {
         return result
       }
 
+      // The loaded manifest lists the files and sets up the run.
+      case 'project': {
+        const projectResult = (await tauri.invoke('configure_manifest', {
+          timeTravel: this.timeTravel
+        })) as ProjectResult
+
+        const result = projectAssemblerResult(projectResult, this.path)
+
+        if (result.status === 'Success') {
+          this.breakpoints = new Breakpoints(result.breakpoints)
+        }
+
+        return result
+      }
+
       default:
         break
     }
@@ -118,6 +136,18 @@ export class TauriExecution implements MipsExecution {
     })
   }
 
+  public async setLogpoints(logpoints: Record<number, string>) {
+    if (!this.configured) {
+      return
+    }
+
+    const mapped = this.breakpoints?.mapLogpoints(logpoints) ?? new Map()
+
+    await tauri.invoke('set_logpoints', {
+      logpoints: Object.fromEntries(mapped),
+    })
+  }
+
   public async pause() {
     await tauri.invoke('pause')
   }
@@ -127,13 +157,13 @@ export class TauriExecution implements MipsExecution {
   }
 
   public async postKey(key: string, up: boolean) {
-    if (key.length !== 1) {
-      return
-    }
-
     await tauri.invoke('post_key', { key, up })
   }
 
+  public async postMouse(x: number, y: number, buttons: number) {
+    await tauri.invoke('post_mouse', { x, y, buttons })
+  }
+
   public async postInput(text: string) {
     if (text.length <= 0) {
       return
@@ -174,6 +204,26 @@ export class TauriExecution implements MipsExecution {
     return new Uint8Array(await result.arrayBuffer())
   }
 
+  async readDisplayDelta(width: number, height: number, address: number): Promise<Uint8Array | null> {
+    const result = await fetch(this.protocol, {
+      headers: {
+        width: width.toString(),
+        height: height.toString(),
+        address: address.toString(),
+        delta: '1',
+      },
+      mode: 'cors',
+      cache: 'no-cache',
+    })
+
+    // Reads of any other display come back whole.
+    if (!result.headers.has('delta')) {
+      return null
+    }
+
+    return new Uint8Array(await result.arrayBuffer())
+  }
+
   public constructor(
     public text: string,
     public path: string | null,
@@ -182,11 +232,7 @@ export class TauriExecution implements MipsExecution {
   ) {
     switch (profile.kind) {
       case 'elf': {
-        const breakpoints = Object.entries(profile.breakpoints).map(
-          ([pc, line]) => ({ line, pcs: [parseInt(pc)] } as Breakpoint)
-        )
-
-        this.breakpoints = new Breakpoints(breakpoints)
+        this.breakpoints = new Breakpoints(elfBreakpoints(profile))
 
         break
       }
@@ -201,6 +247,7 @@ export class TauriExecution implements MipsExecution {
 interface PrintPayload {
   text: string
   error: boolean
+  log: boolean
 }
 
 export class TauriBackend implements MipsBackend {
@@ -213,7 +260,7 @@ export class TauriBackend implements MipsBackend {
       await listen('print', (event) => {
         let payload = event.payload as PrintPayload
 
-        callbacks.consoleWrite(payload.text, payload.error)
+        callbacks.consoleWrite(payload.text, payload.error, payload.log)
       }),
 
       await listen('play-midi', async (event) => {
@@ -252,8 +299,14 @@ export class TauriBackend implements MipsBackend {
     return result as AssemblerResult
   }
 
-  async assembleWithBinary(text: string, path: string | null): Promise<BinaryResult> {
-    const result = (await tauri.invoke('assemble_binary', { text, path })) as [
+  async assembleProject(files: ProjectFile[]): Promise<ProjectResult> {
+    const result = await tauri.invoke('assemble_project', { files })
+
+    return result as ProjectResult
+  }
+
+  async assembleWithBinary(text: string, path: string | null, includeSource: boolean = false): Promise<BinaryResult> {
+    const result = (await tauri.invoke('assemble_binary', { text, path, includeSource })) as [
       number[] | null,
       AssemblerResult
     ]
@@ -271,6 +324,7 @@ export class TauriBackend implements MipsBackend {
       width: config.width,
       height: config.height,
       address: config.address,
+      options: displayOptions(config),
     })
   }
 
@@ -278,16 +332,16 @@ export class TauriBackend implements MipsBackend {
     return await tauri.invoke('decode_instruction', { pc, instruction }) ?? null
   }
 
-  async disassembleElf(named: string, elf: ArrayBuffer): Promise<DisassembleResult> {
+  async disassembleElf(named: string, elf: ArrayBuffer, format?: BinaryFormat): Promise<DisassembleResult> {
     const bytes = Array.from(new Uint8Array(elf))
 
-    const value = await tauri.invoke('disassemble', { named, bytes })
+    const value = await tauri.invoke('disassemble', { named, bytes, format })
 
     return value as DisassembleResult
   }
 
-  async disassemblyDetails(bytes: ArrayBuffer): Promise<InstructionLine[]> {
-    return await tauri.invoke('detailed_disassemble', { bytes: Array.from(new Uint8Array(bytes)) })
+  async disassemblyDetails(bytes: ArrayBuffer, format?: BinaryFormat): Promise<InstructionLine[]> {
+    return await tauri.invoke('detailed_disassemble', { bytes: Array.from(new Uint8Array(bytes)), format })
   }
 
   async lastDisplay(): Promise<LastDisplay> {
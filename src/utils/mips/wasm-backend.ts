@@ -1,9 +1,11 @@
 import {
   AssemblerResult,
+  BinaryFormat,
   BinaryResult,
   BitmapConfig,
   Breakpoints,
   DisassembleResult,
+  elfBreakpoints,
   ExecutionProfile,
   ExecutionResult,
   HexBinaryResult,
@@ -11,7 +13,10 @@ import {
   InstructionLine,
   LastDisplay,
   MipsBackend, MipsCallbacks,
-  MipsExecution
+  MipsExecution,
+  ProjectFile,
+  projectAssemblerResult,
+  ProjectResult
 } from './mips'
 import WasmWorker from './wasm-worker?worker'
 import { ExportRegionsOptions } from '../settings'
@@ -75,7 +80,7 @@ export class WasmBackend implements MipsBackend {
 
     switch (data.op) {
       case MessageEventOp.ConsoleWrite:
-        this.callbacks.consoleWrite(data.text, data.error)
+        this.callbacks.consoleWrite(data.text, data.error, data.log)
         break
       case MessageEventOp.MidiPlay:
         this.callbacks.midiPlay(data.note)
@@ -135,13 +140,21 @@ export class WasmBackend implements MipsBackend {
     })
   }
 
+  async assembleProject(files: ProjectFile[]): Promise<ProjectResult> {
+    return await this.sendRequest<ProjectResult>({
+      op: MessageOp.AssembleProject,
+      files,
+    })
+  }
+
   async assembleWithBinary(
     text: string,
-    path: string | null
+    path: string | null,
+    includeSource: boolean = false
   ): Promise<BinaryResult> {
     const [binary, assemblerResult] = await this.sendRequest<
       [number[] | null, AssemblerResult]
-    >({ op: MessageOp.AssembleBinary, text, path })
+    >({ op: MessageOp.AssembleBinary, text, path, includeSource })
 
     return {
       binary: binary ? Uint8Array.from(binary) : null,
@@ -162,19 +175,22 @@ export class WasmBackend implements MipsBackend {
 
   async disassembleElf(
     named: string,
-    elf: ArrayBuffer
+    elf: ArrayBuffer,
+    format?: BinaryFormat
   ): Promise<DisassembleResult> {
     return await this.sendRequest<DisassembleResult>({
       op: MessageOp.Disassemble,
       named,
       bytes: new Uint8Array(elf),
+      format: format ?? null,
     })
   }
 
-  async disassemblyDetails(bytes: ArrayBuffer): Promise<InstructionLine[]> {
+  async disassemblyDetails(bytes: ArrayBuffer, format?: BinaryFormat): Promise<InstructionLine[]> {
     return await this.sendRequest<InstructionLine[]>({
       op: MessageOp.DetailedDisassemble,
       bytes: new Uint8Array(bytes),
+      format: format ?? null,
     })
   }
 
@@ -232,20 +248,25 @@ export class WasmExecution implements MipsExecution {
           bytes[i] = text.charCodeAt(i)
         }
 
-        const result = await this.backend.sendRequest<boolean>({
+        const result = await this.backend.sendRequest<AssemblerResult | null>({
           op: MessageOp.ConfigureElf,
           bytes,
-          timeTravel: this.timeTravel
+          timeTravel: this.timeTravel,
+          format: this.profile.format ?? null
         })
 
-        return result
-          ? { status: 'Success', breakpoints: [] }
-          : {
-            status: 'Error',
-            message: 'Configured ELF was not valid',
-            body: null,
-            marker: null,
-          }
+        if (result) {
+          this.breakpoints = new Breakpoints(elfBreakpoints(this.profile))
+        }
+
+        // Breakpoints and symbols are filled in for ELF files exported by Saturn.
+        return result ?? {
+          status: 'Error',
+          message: 'Configured ELF was not valid',
+          body: null,
+          marker: null,
+          diagnostics: [],
+        }
       }
 
       case 'asm': {
@@ -262,6 +283,22 @@ export class WasmExecution implements MipsExecution {
         return result
       }
 
+      case 'project': {
+        const projectResult = await this.backend.sendRequest<ProjectResult>({
+          op: MessageOp.ConfigureProject,
+          files: this.profile.files,
+          timeTravel: this.timeTravel
+        })
+
+        const result = projectAssemblerResult(projectResult, this.path)
+
+        if (result.status === 'Success') {
+          this.breakpoints = new Breakpoints(result.breakpoints)
+        }
+
+        return result
+      }
+
       default:
         throw new Error()
     }
@@ -288,6 +325,13 @@ export class WasmExecution implements MipsExecution {
     })
   }
 
+  setLogpoints(logpoints: Record<number, string>): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.SetLogpoints,
+      logpoints: this.breakpoints?.mapLogpoints(logpoints) ?? new Map()
+    })
+  }
+
   setMemory(address: number, bytes: number[]): Promise<void> {
     return this.backend.sendRequest({
       op: MessageOp.WriteBytes,
@@ -319,6 +363,15 @@ export class WasmExecution implements MipsExecution {
     })
   }
 
+  postMouse(x: number, y: number, buttons: number): Promise<void> {
+    return this.backend.sendRequest({
+      op: MessageOp.PostMouse,
+      x,
+      y,
+      buttons
+    })
+  }
+
   pause(): Promise<void> {
     return this.backend.sendRequest({ op: MessageOp.Pause })
   }
@@ -348,7 +401,18 @@ export class WasmExecution implements MipsExecution {
       op: MessageOp.ReadDisplay,
       width,
       height,
-      address
+      address,
+      delta: false
+    })
+  }
+
+  readDisplayDelta(width: number, height: number, address: number): Promise<Uint8Array | null> {
+    return this.backend.sendRequest<Uint8Array | null>({
+      op: MessageOp.ReadDisplay,
+      width,
+      height,
+      address,
+      delta: true
     })
   }
 
@@ -1,5 +1,5 @@
 import { type ExportRegionsOptions } from '../settings'
-import { type BitmapConfig } from './mips'
+import { type BinaryFormat, type BitmapConfig, type ProjectFile } from './mips'
 import { type MidiNote } from '../midi'
 
 export enum MessageOp {
@@ -26,6 +26,10 @@ export enum MessageOp {
   WakeSync,
   Rewind,
   ReadDisplay,
+  AssembleProject,
+  ConfigureProject,
+  SetLogpoints,
+  PostMouse,
 }
 
 export interface AssembleRegionsData {
@@ -43,11 +47,18 @@ export interface AssembleTextData {
   path: string | null
 }
 
+export interface AssembleProjectData {
+  op: MessageOp.AssembleProject
+
+  files: ProjectFile[]
+}
+
 export interface AssembleBinaryData {
   op: MessageOp.AssembleBinary
 
   text: string
   path: string | null
+  includeSource: boolean
 }
 
 export interface DecodeInstructionData {
@@ -62,12 +73,14 @@ export interface DisassembleData {
 
   named: string | null
   bytes: Uint8Array
+  format: BinaryFormat | null
 }
 
 export interface DetailedDisassembleData {
   op: MessageOp.DetailedDisassemble
 
   bytes: Uint8Array
+  format: BinaryFormat | null
 }
 
 export interface ConfigureDisplayData {
@@ -85,6 +98,7 @@ export interface ConfigureElfData {
 
   bytes: Uint8Array
   timeTravel: boolean
+  format: BinaryFormat | null
 }
 
 export interface ConfigureAsmData {
@@ -94,6 +108,13 @@ export interface ConfigureAsmData {
   timeTravel: boolean
 }
 
+export interface ConfigureProjectData {
+  op: MessageOp.ConfigureProject
+
+  files: ProjectFile[]
+  timeTravel: boolean
+}
+
 export interface ResumeData {
   op: MessageOp.Resume
 
@@ -136,6 +157,11 @@ export interface SetBreakpointsData {
   breakpoints: Uint32Array
 }
 
+export interface SetLogpointsData {
+  op: MessageOp.SetLogpoints
+  logpoints: Map<number, string>
+}
+
 export interface PostInputData {
   op: MessageOp.PostInput
   text: string
@@ -147,6 +173,13 @@ export interface PostKeyData {
   up: boolean
 }
 
+export interface PostMouseData {
+  op: MessageOp.PostMouse
+  x: number
+  y: number
+  buttons: number
+}
+
 export interface WakeSyncData {
   op: MessageOp.WakeSync
 }
@@ -161,11 +194,14 @@ export interface ReadDisplayData {
   width: number
   height: number
   address: number
+  // Only rows changed since the last read, for the configured display.
+  delta: boolean
 }
 
 export type MessageData =
   AssembleRegionsData |
   AssembleTextData |
+  AssembleProjectData |
   AssembleBinaryData |
   DecodeInstructionData |
   DisassembleData |
@@ -174,6 +210,7 @@ export type MessageData =
   LastDisplayData |
   ConfigureElfData |
   ConfigureAsmData |
+  ConfigureProjectData |
   ResumeData |
   StopData |
   PauseData |
@@ -182,8 +219,10 @@ export type MessageData =
   WriteBytesData |
   SetRegisterData |
   SetBreakpointsData |
+  SetLogpointsData |
   PostInputData |
   PostKeyData |
+  PostMouseData |
   WakeSyncData |
   RewindData |
   ReadDisplayData
@@ -197,6 +236,7 @@ export interface MessageEventConsoleWrite {
   op: MessageEventOp.ConsoleWrite
   text: string
   error: boolean
+  log: boolean
 }
 
 export interface MessageEventMidiPlay {
@@ -4,20 +4,24 @@ import {
   AssemblerResult,
   BinaryResult,
   DisassembleResult,
+  displayOptions,
   ExecutionModeType,
   ExecutionResult,
   HexBinaryResult,
   InstructionDetails,
   InstructionLine,
-  LastDisplay
+  LastDisplay,
+  ProjectResult
 } from './mips'
 import {
   AssembleBinaryData,
+  AssembleProjectData,
   AssembleRegionsData,
   AssembleTextData,
   ConfigureAsmData,
   ConfigureDisplayData,
   ConfigureElfData,
+  ConfigureProjectData,
   DecodeInstructionData,
   DetailedDisassembleData,
   DisassembleData,
@@ -30,11 +34,13 @@ import {
   MessageResponseKind,
   PostInputData,
   PostKeyData,
+  PostMouseData,
   ReadBytesData,
   ReadDisplayData,
   ResumeData,
   RewindData,
   SetBreakpointsData,
+  SetLogpointsData,
   SetRegisterData,
   WriteBytesData
 } from './wasm-worker-message'
@@ -42,11 +48,12 @@ import { type MidiNote } from '../midi'
 
 backend.initialize()
 
-function sendConsoleWrite(text: string, error: boolean) {
+function sendConsoleWrite(text: string, error: boolean, log: boolean) {
   postEvent({
     op: MessageEventOp.ConsoleWrite,
     text,
-    error
+    error,
+    log
   })
 }
 
@@ -64,7 +71,7 @@ const runner = new backend.Runner(new backend.EventHandler(
 ))
 
 function assembleRegions({ text, options }: AssembleRegionsData): HexBinaryResult {
-  const [regions, result] = backend.assemble_regions(text, options) as [
+  const [regions, result] = runner.assemble_regions(text, options) as [
     AssembledRegions | null,
     AssemblerResult
   ]
@@ -76,41 +83,49 @@ function assembleRegions({ text, options }: AssembleRegionsData): HexBinaryResul
 }
 
 function assembleText({ text }: AssembleTextData): AssemblerResult {
-  return backend.assemble_text(text) as AssemblerResult
+  return runner.assemble_text(text) as AssemblerResult
 }
 
-function assembleBinary({ text }: AssembleBinaryData): BinaryResult {
-  return backend.assemble_binary(text) as BinaryResult
+function assembleProject({ files }: AssembleProjectData): ProjectResult {
+  return runner.assemble_project(files) as ProjectResult
+}
+
+function assembleBinary({ text, includeSource }: AssembleBinaryData): BinaryResult {
+  return runner.assemble_binary(text, includeSource) as BinaryResult
 }
 
 function decodeInstruction({ pc, instruction }: DecodeInstructionData): InstructionDetails | null {
   return backend.decode_instruction(pc, instruction) as InstructionDetails | null
 }
 
-function disassemble({ named, bytes }: DisassembleData): DisassembleResult {
-  return backend.disassemble(named ?? undefined, bytes) as DisassembleResult
+function disassemble({ named, bytes, format }: DisassembleData): DisassembleResult {
+  return backend.disassemble(named ?? undefined, bytes, format) as DisassembleResult
 }
 
-function detailedDisassemble({ bytes }: DetailedDisassembleData): InstructionLine[] {
-  return backend.detailed_disassemble(bytes) as InstructionLine[]
+function detailedDisassemble({ bytes, format }: DetailedDisassembleData): InstructionLine[] {
+  return backend.detailed_disassemble(bytes, format) as InstructionLine[]
 }
 
 function configureDisplay({ config }: ConfigureDisplayData) {
-  runner.configure_display(config.address, config.width, config.height)
+  runner.configure_display(config.address, config.width, config.height, displayOptions(config))
 }
 
 function lastDisplay(): LastDisplay {
   return runner.last_display()
 }
 
-function configureElf({ bytes, timeTravel }: ConfigureElfData): boolean {
-  return runner.configure_elf(bytes, timeTravel)
+function configureElf({ bytes, timeTravel, format }: ConfigureElfData): AssemblerResult | null {
+  return runner.configure_elf(bytes, timeTravel, format)
 }
 
 function configureAsm({ text, timeTravel }: ConfigureAsmData): AssemblerResult {
   return runner.configure_asm(text, timeTravel)
 }
 
+function configureProject({ files, timeTravel }: ConfigureProjectData): ProjectResult {
+  return runner.configure_project(files, timeTravel)
+}
+
 // Thanks to Milo
 // https://github.com/facebook/react/blob/66cf2cfc8a8c4b09d2b783fd7302ae6b24150935/packages/scheduler/src/forks/Scheduler.js#L534-L540
 const channel = new MessageChannel()
@@ -187,6 +202,10 @@ function setBreakpoints({ breakpoints }: SetBreakpointsData) {
   runner.set_breakpoints(breakpoints)
 }
 
+function setLogpoints({ logpoints }: SetLogpointsData) {
+  runner.set_logpoints(logpoints)
+}
+
 function postInput({ text }: PostInputData) {
   runner.post_input(text)
 }
@@ -195,6 +214,10 @@ function postKey({ key, up }: PostKeyData) {
   runner.post_key(key, up)
 }
 
+function postMouse({ x, y, buttons }: PostMouseData) {
+  runner.post_mouse(x, y, buttons)
+}
+
 function wakeSync() {
   runner.wake_sync()
 }
@@ -203,14 +226,19 @@ function rewind({ count }: RewindData): ExecutionResult | null {
   return runner.rewind(count)
 }
 
-function readDisplay({ width, height, address }: ReadDisplayData) {
-  return runner.read_display(address, width, height)
+function readDisplay({ width, height, address, delta }: ReadDisplayData) {
+  const result = delta
+    ? runner.take_display_delta(address, width, height)
+    : runner.read_display(address, width, height)
+
+  return result ?? null
 }
 
 async function dispatchOp(data: MessageData): Promise<any> {
   switch (data.op) {
     case MessageOp.AssembleRegions: return assembleRegions(data)
     case MessageOp.AssembleText: return assembleText(data)
+    case MessageOp.AssembleProject: return assembleProject(data)
     case MessageOp.AssembleBinary: return assembleBinary(data)
     case MessageOp.DecodeInstruction: return decodeInstruction(data)
     case MessageOp.Disassemble: return disassemble(data)
@@ -219,6 +247,7 @@ async function dispatchOp(data: MessageData): Promise<any> {
     case MessageOp.LastDisplay: return lastDisplay()
     case MessageOp.ConfigureElf: return configureElf(data)
     case MessageOp.ConfigureAsm: return configureAsm(data)
+    case MessageOp.ConfigureProject: return configureProject(data)
     case MessageOp.Resume: return await resume(data)
     case MessageOp.Stop: return stop()
     case MessageOp.Pause: return pause()
@@ -227,8 +256,10 @@ async function dispatchOp(data: MessageData): Promise<any> {
     case MessageOp.WriteBytes: return writeBytes(data)
     case MessageOp.SetRegister: return setRegister(data)
     case MessageOp.SetBreakpoints: return setBreakpoints(data)
+    case MessageOp.SetLogpoints: return setLogpoints(data)
     case MessageOp.PostInput: return postInput(data)
     case MessageOp.PostKey: return postKey(data)
+    case MessageOp.PostMouse: return postMouse(data)
     case MessageOp.WakeSync: return wakeSync()
     case MessageOp.Rewind: return rewind(data)
     case MessageOp.ReadDisplay: return readDisplay(data)
@@ -26,6 +26,15 @@ export const elfFilter: AccessFilter[] = [
   },
 ]
 
+// Anything disassemble can read, see BinaryFormat.
+export const binaryFilter: AccessFilter[] = [
+  {
+    name: 'Binary',
+    extensions: ['elf', 'bin', 'hex'],
+  },
+  ...elfFilter,
+]
+
 export async function selectSaveDestination(
   title: string, filters?: AccessFilter[]
 ): Promise<AccessFile<undefined> | null> {
@@ -36,7 +45,7 @@ export async function selectSaveDestination(
 
 export async function selectOpenElf(): Promise<AccessFile<Uint8Array> | null> {
   const result = (await invoke('access_select_open', {
-    title: 'Select ELF', filters: elfFilter, selection: 'all_binary'
+    title: 'Select ELF', filters: binaryFilter, selection: 'all_binary'
   })) as AccessFile<number[]> | null
 
   if (result !== null) {
@@ -53,7 +62,7 @@ export async function selectOpenFile(
   title: string, filters?: AccessFilter[]
 ): Promise<AccessFile<string | Uint8Array> | null> {
   return await invoke('access_select_open', {
-    title, filters: filters ?? [], selection: { binary: ['elf'] }
+    title, filters: filters ?? [], selection: { binary: ['elf', 'bin'] }
   })
 }
 
@@ -0,0 +1,24 @@
+import { invoke } from '@tauri-apps/api'
+
+export const manifestName = 'saturn.toml'
+
+export interface ProjectManifest {
+  name: string | null
+  files: string[]
+  entry: string | null
+}
+
+// files are the manifest's files, resolved inside the project directory.
+export interface LoadedProject {
+  manifest: ProjectManifest
+  files: string[]
+}
+
+// Takes saturn.toml or the directory holding it.
+export async function loadManifest(path: string): Promise<LoadedProject> {
+  return await invoke('load_manifest', { path })
+}
+
+export async function clearManifest(): Promise<void> {
+  await invoke('clear_manifest')
+}
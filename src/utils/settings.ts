@@ -1,13 +1,36 @@
 import { reactive, watch } from 'vue'
-import { BitmapConfig } from './mips/mips'
+import { BitmapConfig, DisplayBase } from './mips/mips'
 import { backend } from '../state/backend'
 
-const settingsVersion = 6
+const settingsVersion = 7
+
+export type ExportRegionsKind =
+  | 'plain'
+  | 'hex_v3'
+  | 'intel_hex'
+  | 'srec'
+  | 'readmemh'
+  | 'readmemb'
+  | 'coe'
+  | 'mif'
+
+// Kinds that group bytes into words, using the encoding option.
+export const wordExportKinds: ExportRegionsKind[] = ['hex_v3', 'readmemh', 'readmemb', 'coe', 'mif']
 
 export interface ExportRegionsOptions {
-  kind: 'plain' | 'hex_v3'
+  kind: ExportRegionsKind
   continuous: boolean
   encoding: 'byte' | 'big32' | 'little32'
+  // Continuous bounds (end is exclusive), default to the lowest and highest region addresses.
+  start?: number | null
+  end?: number | null
+  // Byte written between regions in a continuous export.
+  fill?: number
+}
+
+export interface ElfExportSettings {
+  // Embeds the assembled source in exported ELF files.
+  includeSource: boolean
 }
 
 export interface BitmapSettings {
@@ -16,6 +39,8 @@ export interface BitmapSettings {
   unitWidth: number
   unitHeight: number
   address: number
+  // Set by the base buttons, cleared when the address is typed in.
+  base?: DisplayBase | null
 }
 
 export interface EditorSettings {
@@ -59,6 +84,7 @@ export interface Settings {
   execution: ExecutionSettings
   memory: MemorySettings
   export: ExportRegionsOptions
+  elf: ElfExportSettings
 }
 
 function defaultSettings(): Settings {
@@ -92,6 +118,9 @@ function defaultSettings(): Settings {
       continuous: false,
       kind: 'hex_v3',
       encoding: 'little32'
+    },
+    elf: {
+      includeSource: false
     }
   }
 }
@@ -121,7 +150,10 @@ export function displayConfig(bitmap: BitmapSettings): BitmapConfig {
   return {
     width: Math.ceil(bitmap.displayWidth / bitmap.unitWidth),
     height: Math.ceil(bitmap.displayHeight / bitmap.unitHeight),
+    unitWidth: bitmap.unitWidth,
+    unitHeight: bitmap.unitHeight,
     address: bitmap.address,
+    base: bitmap.base ?? null,
   }
 }
 
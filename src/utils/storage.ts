@@ -56,6 +56,8 @@ export function useStorage(
 
     const result = await backend.assembleText(collectLines(current?.lines ?? []), current?.path ?? null)
 
+    error.dismissHighlight()
+
     if (result.status === 'Error' && result.marker) {
       const tokens = storage.highlights[result.marker.line]
 
@@ -65,8 +67,29 @@ export function useStorage(
         result.marker.offset,
         result.message
       )
-    } else {
-      error.dismissHighlight()
+    }
+
+    // The first error is already shown above, diagnostics from other files are skipped.
+    const others = result.diagnostics
+      .filter((diagnostic) => !diagnostic.path || diagnostic.path === current?.path)
+      .slice(result.status === 'Error' && result.marker ? 1 : 0)
+
+    for (const diagnostic of others) {
+      const tokens = storage.highlights[diagnostic.start.line]
+
+      if (!tokens) {
+        continue
+      }
+
+      const fixes = diagnostic.fixes.map((fix) => ` ${fix.message}`).join('')
+
+      error.addHighlight(
+        diagnostic.start.line,
+        tokens,
+        diagnostic.start.column,
+        `${diagnostic.message}${fixes}`,
+        diagnostic.severity === 'Warning'
+      )
     }
   }
 
@@ -107,6 +130,22 @@ export function useStorage(
     if (moved) {
       current.breakpoints = [...new Set(current.breakpoints)]
     }
+
+    if (current.logpoints) {
+      const logpoints: Record<number, string> = {}
+
+      for (const [key, message] of Object.entries(current.logpoints)) {
+        const point = parseInt(key)
+
+        if (point < line + Math.min(deleted, replaced)) {
+          logpoints[point] = message
+        } else if (!(deleted > replaced && point < line + deleted)) {
+          logpoints[point + replaced - deleted] = message
+        }
+      }
+
+      current.logpoints = logpoints
+    }
   }
 
   function handleDirty(line: number, deleted: number, lines: string[]) {
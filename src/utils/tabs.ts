@@ -3,6 +3,7 @@ import { computed, ComputedRef, reactive, ref, Ref, watch } from 'vue'
 import { v4 as uuid } from 'uuid'
 import {
   AssemblyExecutionProfile,
+  BinaryFormat,
   ElfExecutionProfile,
   ExecutionProfile,
 } from './mips/mips'
@@ -55,6 +56,7 @@ export interface EditorTab {
   removed: boolean
   cursor: CursorState
   breakpoints: number[]
+  logpoints?: Record<number, string> // line -> message template
   path: string | null
   writable: boolean
   marked: boolean // needs saving
@@ -79,7 +81,7 @@ export interface TabsInterface {
     path?: string,
     profile?: ExecutionProfile
   ): void
-  loadElf(named: string, elf: ArrayBuffer): Promise<void>
+  loadElf(named: string, elf: ArrayBuffer, format?: BinaryFormat): Promise<void>
 }
 
 export type TabsResult = TabsInterface & {
@@ -350,8 +352,8 @@ export function useTabs(): TabsResult {
     editor.selected = id
   }
 
-  async function loadElf(named: string, elf: ArrayBuffer) {
-    const value = await backend.disassembleElf(named, elf)
+  async function loadElf(named: string, elf: ArrayBuffer, format?: BinaryFormat) {
+    const value = await backend.disassembleElf(named, elf, format)
 
     const bytes = new Uint8Array(elf)
     let binary = ''
@@ -362,6 +364,8 @@ export function useTabs(): TabsResult {
       kind: 'elf',
       elf: window.btoa(binary),
       breakpoints: value.breakpoints,
+      symbolLines: value.symbol_lines,
+      format,
     } as ElfExecutionProfile
 
     createTab(named, lines, null, profile, false)